@@ -0,0 +1,203 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::io::IsTerminal;
+
+/// How many colors the terminal can display. Ordered from least to most capable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ColorSupport {
+    None,
+    Ansi16,
+    Ansi256,
+    Truecolor,
+}
+
+/// `color_mode` in the config and `--color-mode` on the command line. `Auto` detects support from the environment.
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColorMode {
+    /// detect from NO_COLOR, COLORTERM, TERM and whether stdout is a terminal
+    #[default]
+    Auto,
+    /// no colors or text attributes
+    None,
+    /// the 16 named ANSI colors
+    #[value(name = "16")]
+    #[serde(rename = "16")]
+    Ansi16,
+    /// the 256 color palette
+    #[value(name = "256")]
+    #[serde(rename = "256")]
+    Ansi256,
+    /// 24 bit rgb colors
+    Truecolor,
+}
+
+/// the environment variables (and tty check) that color detection looks at
+#[derive(Default)]
+struct ColorEnv {
+    no_color: Option<String>,
+    clicolor_force: Option<String>,
+    colorterm: Option<String>,
+    term: Option<String>,
+    term_program: Option<String>,
+    wt_session: Option<String>,
+    is_tty: bool,
+}
+
+impl ColorEnv {
+    fn from_env() -> Self {
+        ColorEnv {
+            no_color: env::var("NO_COLOR").ok(),
+            clicolor_force: env::var("CLICOLOR_FORCE").ok(),
+            colorterm: env::var("COLORTERM").ok(),
+            term: env::var("TERM").ok(),
+            term_program: env::var("TERM_PROGRAM").ok(),
+            wt_session: env::var("WT_SESSION").ok(),
+            is_tty: std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+fn is_set(var: &Option<String>) -> bool {
+    var.as_deref().is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+fn detect(env: &ColorEnv) -> ColorSupport {
+    // https://no-color.org: any non-empty value disables color, even "0"
+    if env.no_color.as_deref().is_some_and(|v| !v.is_empty()) {
+        return ColorSupport::None;
+    }
+    if !env.is_tty && !is_set(&env.clicolor_force) {
+        return ColorSupport::None;
+    }
+
+    let colorterm = env.colorterm.as_deref().unwrap_or_default();
+    let term = env.term.as_deref().unwrap_or_default();
+    let term_program = env.term_program.as_deref().unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        ColorSupport::Truecolor
+    } else if term_program == "Apple_Terminal" {
+        // Terminal.app understands 256 colors but garbles truecolor
+        ColorSupport::Ansi256
+    } else if ["iTerm.app", "WezTerm", "vscode"].contains(&term_program)
+        || env.wt_session.is_some()
+        || term.contains("truecolor")
+        || term.contains("24bit")
+        || term.ends_with("-direct")
+    {
+        ColorSupport::Truecolor
+    } else if term.contains("256color") {
+        ColorSupport::Ansi256
+    } else if term == "dumb" {
+        ColorSupport::None
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+impl ColorMode {
+    pub(crate) fn resolve(&self) -> ColorSupport {
+        match self {
+            ColorMode::Auto => detect(&ColorEnv::from_env()),
+            ColorMode::None => ColorSupport::None,
+            ColorMode::Ansi16 => ColorSupport::Ansi16,
+            ColorMode::Ansi256 => ColorSupport::Ansi256,
+            ColorMode::Truecolor => ColorSupport::Truecolor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tty_env() -> ColorEnv {
+        ColorEnv {
+            is_tty: true,
+            ..ColorEnv::default()
+        }
+    }
+
+    #[test]
+    fn no_color_wins() {
+        let env = ColorEnv {
+            no_color: Some("1".to_string()),
+            colorterm: Some("truecolor".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::None);
+        let env = ColorEnv {
+            no_color: Some("0".to_string()),
+            colorterm: Some("truecolor".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::None);
+
+        // empty NO_COLOR is ignored
+        let env = ColorEnv {
+            no_color: Some("".to_string()),
+            colorterm: Some("truecolor".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::Truecolor);
+    }
+
+    #[test]
+    fn non_tty_has_no_color_unless_forced() {
+        let env = ColorEnv {
+            colorterm: Some("truecolor".to_string()),
+            ..ColorEnv::default()
+        };
+        assert_eq!(detect(&env), ColorSupport::None);
+
+        let env = ColorEnv {
+            colorterm: Some("truecolor".to_string()),
+            clicolor_force: Some("1".to_string()),
+            ..ColorEnv::default()
+        };
+        assert_eq!(detect(&env), ColorSupport::Truecolor);
+    }
+
+    #[test]
+    fn detects_terminals() {
+        let env = ColorEnv {
+            term_program: Some("Apple_Terminal".to_string()),
+            term: Some("xterm-256color".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::Ansi256);
+
+        let env = ColorEnv {
+            term_program: Some("iTerm.app".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::Truecolor);
+
+        let env = ColorEnv {
+            term: Some("xterm-256color".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::Ansi256);
+
+        let env = ColorEnv {
+            term: Some("xterm".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::Ansi16);
+
+        let env = ColorEnv {
+            term: Some("dumb".to_string()),
+            ..tty_env()
+        };
+        assert_eq!(detect(&env), ColorSupport::None);
+    }
+
+    #[test]
+    fn deserialize_color_mode() {
+        let mode: ColorMode = serde_json::from_str(r#""256""#).unwrap();
+        assert_eq!(mode, ColorMode::Ansi256);
+        let mode: ColorMode = serde_json::from_str(r#""truecolor""#).unwrap();
+        assert_eq!(mode, ColorMode::Truecolor);
+    }
+}
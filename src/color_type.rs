@@ -1,9 +1,13 @@
+use crate::color_support::ColorSupport;
 use std::str::FromStr;
 
-#[cfg_attr(test, derive(PartialEq, Debug))]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) enum ColorType {
     Rgb(u8, u8, u8),
+    /// one of the 16 named ANSI colors, e.g. "bright red"
     Ansi(String),
+    /// index into the 256 color palette
+    Ansi256(u8),
 }
 
 impl Default for ColorType {
@@ -12,12 +16,43 @@ impl Default for ColorType {
     }
 }
 
+/// parse the comma separated arguments of a function-like color, e.g. `rgb(1, 2, 3)`
+fn parse_color_fn_args<'a>(s: &'a str, name: &str) -> Vec<&'a str> {
+    let s = s.trim_start_matches(name);
+    let s = s.trim_start_matches(' ').trim_start_matches('(');
+    let s = s.trim_end_matches(' ').trim_end_matches(')');
+    s.split(',').map(|arg| arg.trim()).collect()
+}
+
+fn is_ansi_name(s: &str) -> bool {
+    !s.starts_with('#') && colored::Color::from_str(s).is_ok()
+}
+
+fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (u8, u8, u8) {
+    let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+    let h = (h % 360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u8 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = l - c / 2.0;
+    let to_u8 = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
 impl FromStr for ColorType {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim().to_lowercase();
         let invalid_rgb_msg = "invalid RGB color";
         let invalid_hex_msg = "invalid hex color";
+        let invalid_ansi_msg = "invalid ansi color, expected ansi(0) through ansi(255)";
+        let invalid_hsl_msg = "invalid HSL color, expected e.g. hsl(30, 100%, 50%)";
         if s.starts_with('#') {
             let s = s.trim_start_matches('#');
             if s.len() != 6 {
@@ -51,12 +86,287 @@ impl FromStr for ColorType {
                 .parse::<u8>()
                 .map_err(|_| invalid_rgb_msg)?;
             Ok(ColorType::Rgb(r, g, b))
-        } else {
+        } else if s.starts_with("ansi") {
+            let args = parse_color_fn_args(&s, "ansi");
+            let [index] = args[..] else {
+                return Err(invalid_ansi_msg.into());
+            };
+            let index = index.parse::<u8>().map_err(|_| invalid_ansi_msg)?;
+            Ok(ColorType::Ansi256(index))
+        } else if s.starts_with("hsl") {
+            let args = parse_color_fn_args(&s, "hsl");
+            let [h, sat, l] = args[..] else {
+                return Err(invalid_hsl_msg.into());
+            };
+            let parse_percent = |v: &str| {
+                v.trim_end_matches('%')
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| (0.0..=100.0).contains(v))
+                    .map(|v| v / 100.0)
+                    .ok_or(invalid_hsl_msg)
+            };
+            let h = h.parse::<f32>().map_err(|_| invalid_hsl_msg)?;
+            if !(0.0..=360.0).contains(&h) {
+                return Err(invalid_hsl_msg.into());
+            }
+            let (r, g, b) = hsl_to_rgb(h, parse_percent(sat)?, parse_percent(l)?);
+            Ok(ColorType::Rgb(r, g, b))
+        } else if is_ansi_name(&s) {
             Ok(ColorType::Ansi(s))
+        } else if let Some((_, (r, g, b))) = CSS_COLORS.iter().find(|(name, _)| *name == s) {
+            // ansi names win over css names, so "red" is still the terminal's red
+            Ok(ColorType::Rgb(*r, *g, *b))
+        } else {
+            Err(format!("unknown color: \"{s}\""))
+        }
+    }
+}
+
+type Rgb = (u8, u8, u8);
+
+// xterm's default values for the 16 named colors. Terminals let users customize these, so this is only an approximation
+const ANSI16_PALETTE: [(&str, Rgb); 16] = [
+    ("black", (0, 0, 0)),
+    ("red", (205, 0, 0)),
+    ("green", (0, 205, 0)),
+    ("yellow", (205, 205, 0)),
+    ("blue", (0, 0, 238)),
+    ("magenta", (205, 0, 205)),
+    ("cyan", (0, 205, 205)),
+    ("white", (229, 229, 229)),
+    ("bright black", (127, 127, 127)),
+    ("bright red", (255, 0, 0)),
+    ("bright green", (0, 255, 0)),
+    ("bright yellow", (255, 255, 0)),
+    ("bright blue", (92, 92, 255)),
+    ("bright magenta", (255, 0, 255)),
+    ("bright cyan", (0, 255, 255)),
+    ("bright white", (255, 255, 255)),
+];
+
+// channel values of the 6x6x6 color cube in the 256 color palette (indexes 16-231)
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn ansi256_to_rgb(index: u8) -> Rgb {
+    match index {
+        0..=15 => ANSI16_PALETTE[index as usize].1,
+        16..=231 => {
+            let i = index - 16;
+            (
+                CUBE_LEVELS[(i / 36) as usize],
+                CUBE_LEVELS[((i / 6) % 6) as usize],
+                CUBE_LEVELS[(i % 6) as usize],
+            )
+        }
+        // grayscale ramp
+        _ => {
+            let v = 8 + (index - 232) * 10;
+            (v, v, v)
         }
     }
 }
 
+fn nearest_ansi256(rgb: Rgb) -> u8 {
+    let nearest_level = |v: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * nearest_level(rgb.0) + 6 * nearest_level(rgb.1) + nearest_level(rgb.2);
+    let avg = ((rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3) as u8;
+    let gray = 232 + (avg.saturating_sub(3) / 10).min(23);
+    if distance(rgb, ansi256_to_rgb(gray)) < distance(rgb, ansi256_to_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn nearest_ansi16(rgb: Rgb) -> &'static str {
+    ANSI16_PALETTE
+        .iter()
+        .min_by_key(|(_, palette_rgb)| distance(rgb, *palette_rgb))
+        .map(|(name, _)| *name)
+        .unwrap_or("white")
+}
+
+impl ColorType {
+    /// map this color to the nearest one the terminal can display
+    pub(crate) fn downgrade(&self, support: ColorSupport) -> ColorType {
+        match (self, support) {
+            (ColorType::Rgb(r, g, b), ColorSupport::Ansi256) => {
+                ColorType::Ansi256(nearest_ansi256((*r, *g, *b)))
+            }
+            (ColorType::Rgb(r, g, b), ColorSupport::Ansi16 | ColorSupport::None) => {
+                ColorType::Ansi(nearest_ansi16((*r, *g, *b)).to_string())
+            }
+            (ColorType::Ansi256(index), ColorSupport::Ansi16 | ColorSupport::None) => {
+                ColorType::Ansi(nearest_ansi16(ansi256_to_rgb(*index)).to_string())
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// CSS named colors, see https://www.w3.org/TR/css-color-4/#named-colors
+const CSS_COLORS: &[(&str, (u8, u8, u8))] = &[
+    ("aliceblue", (240, 248, 255)),
+    ("antiquewhite", (250, 235, 215)),
+    ("aqua", (0, 255, 255)),
+    ("aquamarine", (127, 255, 212)),
+    ("azure", (240, 255, 255)),
+    ("beige", (245, 245, 220)),
+    ("bisque", (255, 228, 196)),
+    ("black", (0, 0, 0)),
+    ("blanchedalmond", (255, 235, 205)),
+    ("blue", (0, 0, 255)),
+    ("blueviolet", (138, 43, 226)),
+    ("brown", (165, 42, 42)),
+    ("burlywood", (222, 184, 135)),
+    ("cadetblue", (95, 158, 160)),
+    ("chartreuse", (127, 255, 0)),
+    ("chocolate", (210, 105, 30)),
+    ("coral", (255, 127, 80)),
+    ("cornflowerblue", (100, 149, 237)),
+    ("cornsilk", (255, 248, 220)),
+    ("crimson", (220, 20, 60)),
+    ("cyan", (0, 255, 255)),
+    ("darkblue", (0, 0, 139)),
+    ("darkcyan", (0, 139, 139)),
+    ("darkgoldenrod", (184, 134, 11)),
+    ("darkgray", (169, 169, 169)),
+    ("darkgreen", (0, 100, 0)),
+    ("darkgrey", (169, 169, 169)),
+    ("darkkhaki", (189, 183, 107)),
+    ("darkmagenta", (139, 0, 139)),
+    ("darkolivegreen", (85, 107, 47)),
+    ("darkorange", (255, 140, 0)),
+    ("darkorchid", (153, 50, 204)),
+    ("darkred", (139, 0, 0)),
+    ("darksalmon", (233, 150, 122)),
+    ("darkseagreen", (143, 188, 143)),
+    ("darkslateblue", (72, 61, 139)),
+    ("darkslategray", (47, 79, 79)),
+    ("darkslategrey", (47, 79, 79)),
+    ("darkturquoise", (0, 206, 209)),
+    ("darkviolet", (148, 0, 211)),
+    ("deeppink", (255, 20, 147)),
+    ("deepskyblue", (0, 191, 255)),
+    ("dimgray", (105, 105, 105)),
+    ("dimgrey", (105, 105, 105)),
+    ("dodgerblue", (30, 144, 255)),
+    ("firebrick", (178, 34, 34)),
+    ("floralwhite", (255, 250, 240)),
+    ("forestgreen", (34, 139, 34)),
+    ("fuchsia", (255, 0, 255)),
+    ("gainsboro", (220, 220, 220)),
+    ("ghostwhite", (248, 248, 255)),
+    ("gold", (255, 215, 0)),
+    ("goldenrod", (218, 165, 32)),
+    ("gray", (128, 128, 128)),
+    ("green", (0, 128, 0)),
+    ("greenyellow", (173, 255, 47)),
+    ("grey", (128, 128, 128)),
+    ("honeydew", (240, 255, 240)),
+    ("hotpink", (255, 105, 180)),
+    ("indianred", (205, 92, 92)),
+    ("indigo", (75, 0, 130)),
+    ("ivory", (255, 255, 240)),
+    ("khaki", (240, 230, 140)),
+    ("lavender", (230, 230, 250)),
+    ("lavenderblush", (255, 240, 245)),
+    ("lawngreen", (124, 252, 0)),
+    ("lemonchiffon", (255, 250, 205)),
+    ("lightblue", (173, 216, 230)),
+    ("lightcoral", (240, 128, 128)),
+    ("lightcyan", (224, 255, 255)),
+    ("lightgoldenrodyellow", (250, 250, 210)),
+    ("lightgray", (211, 211, 211)),
+    ("lightgreen", (144, 238, 144)),
+    ("lightgrey", (211, 211, 211)),
+    ("lightpink", (255, 182, 193)),
+    ("lightsalmon", (255, 160, 122)),
+    ("lightseagreen", (32, 178, 170)),
+    ("lightskyblue", (135, 206, 250)),
+    ("lightslategray", (119, 136, 153)),
+    ("lightslategrey", (119, 136, 153)),
+    ("lightsteelblue", (176, 196, 222)),
+    ("lightyellow", (255, 255, 224)),
+    ("lime", (0, 255, 0)),
+    ("limegreen", (50, 205, 50)),
+    ("linen", (250, 240, 230)),
+    ("magenta", (255, 0, 255)),
+    ("maroon", (128, 0, 0)),
+    ("mediumaquamarine", (102, 205, 170)),
+    ("mediumblue", (0, 0, 205)),
+    ("mediumorchid", (186, 85, 211)),
+    ("mediumpurple", (147, 112, 219)),
+    ("mediumseagreen", (60, 179, 113)),
+    ("mediumslateblue", (123, 104, 238)),
+    ("mediumspringgreen", (0, 250, 154)),
+    ("mediumturquoise", (72, 209, 204)),
+    ("mediumvioletred", (199, 21, 133)),
+    ("midnightblue", (25, 25, 112)),
+    ("mintcream", (245, 255, 250)),
+    ("mistyrose", (255, 228, 225)),
+    ("moccasin", (255, 228, 181)),
+    ("navajowhite", (255, 222, 173)),
+    ("navy", (0, 0, 128)),
+    ("oldlace", (253, 245, 230)),
+    ("olive", (128, 128, 0)),
+    ("olivedrab", (107, 142, 35)),
+    ("orange", (255, 165, 0)),
+    ("orangered", (255, 69, 0)),
+    ("orchid", (218, 112, 214)),
+    ("palegoldenrod", (238, 232, 170)),
+    ("palegreen", (152, 251, 152)),
+    ("paleturquoise", (175, 238, 238)),
+    ("palevioletred", (219, 112, 147)),
+    ("papayawhip", (255, 239, 213)),
+    ("peachpuff", (255, 218, 185)),
+    ("peru", (205, 133, 63)),
+    ("pink", (255, 192, 203)),
+    ("plum", (221, 160, 221)),
+    ("powderblue", (176, 224, 230)),
+    ("purple", (128, 0, 128)),
+    ("rebeccapurple", (102, 51, 153)),
+    ("red", (255, 0, 0)),
+    ("rosybrown", (188, 143, 143)),
+    ("royalblue", (65, 105, 225)),
+    ("saddlebrown", (139, 69, 19)),
+    ("salmon", (250, 128, 114)),
+    ("sandybrown", (244, 164, 96)),
+    ("seagreen", (46, 139, 87)),
+    ("seashell", (255, 245, 238)),
+    ("sienna", (160, 82, 45)),
+    ("silver", (192, 192, 192)),
+    ("skyblue", (135, 206, 235)),
+    ("slateblue", (106, 90, 205)),
+    ("slategray", (112, 128, 144)),
+    ("slategrey", (112, 128, 144)),
+    ("snow", (255, 250, 250)),
+    ("springgreen", (0, 255, 127)),
+    ("steelblue", (70, 130, 180)),
+    ("tan", (210, 180, 140)),
+    ("teal", (0, 128, 128)),
+    ("thistle", (216, 191, 216)),
+    ("tomato", (255, 99, 71)),
+    ("turquoise", (64, 224, 208)),
+    ("violet", (238, 130, 238)),
+    ("wheat", (245, 222, 179)),
+    ("white", (255, 255, 255)),
+    ("whitesmoke", (245, 245, 245)),
+    ("yellow", (255, 255, 0)),
+    ("yellowgreen", (154, 205, 50)),
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -94,5 +404,111 @@ mod tests {
     fn test_ansi() {
         let rgb = ColorType::from_str("red").unwrap();
         assert_eq!(rgb, ColorType::Ansi("red".to_string()));
+        let rgb = ColorType::from_str("Bright Red").unwrap();
+        assert_eq!(rgb, ColorType::Ansi("bright red".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_color_is_err() {
+        assert!(ColorType::from_str("blu").is_err());
+        assert!(ColorType::from_str("bright").is_err());
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(
+            ColorType::from_str("ansi(208)"),
+            Ok(ColorType::Ansi256(208))
+        );
+        assert_eq!(
+            ColorType::from_str(" ansi ( 0 ) "),
+            Ok(ColorType::Ansi256(0))
+        );
+        assert!(ColorType::from_str("ansi(256)").is_err());
+        assert!(ColorType::from_str("ansi(1, 2)").is_err());
+        assert!(ColorType::from_str("ansi()").is_err());
+    }
+
+    #[test]
+    fn test_hsl() {
+        assert_eq!(
+            ColorType::from_str("hsl(0, 100%, 50%)"),
+            Ok(ColorType::Rgb(255, 0, 0))
+        );
+        assert_eq!(
+            ColorType::from_str("hsl(120, 100, 25)"),
+            Ok(ColorType::Rgb(0, 128, 0))
+        );
+        assert_eq!(
+            ColorType::from_str("hsl(240, 100%, 50%)"),
+            Ok(ColorType::Rgb(0, 0, 255))
+        );
+        assert_eq!(
+            ColorType::from_str("hsl(30, 0%, 100%)"),
+            Ok(ColorType::Rgb(255, 255, 255))
+        );
+        assert_eq!(
+            ColorType::from_str("hsl(360, 100%, 50%)"),
+            Ok(ColorType::Rgb(255, 0, 0))
+        );
+        assert!(ColorType::from_str("hsl(400, 100%, 50%)").is_err());
+        assert!(ColorType::from_str("hsl(0, 120%, 50%)").is_err());
+        assert!(ColorType::from_str("hsl(0, 100%)").is_err());
+    }
+
+    #[test]
+    fn test_ansi256_to_rgb() {
+        assert_eq!(ansi256_to_rgb(1), (205, 0, 0));
+        assert_eq!(ansi256_to_rgb(16), (0, 0, 0));
+        assert_eq!(ansi256_to_rgb(208), (255, 135, 0));
+        assert_eq!(ansi256_to_rgb(231), (255, 255, 255));
+        assert_eq!(ansi256_to_rgb(232), (8, 8, 8));
+        assert_eq!(ansi256_to_rgb(255), (238, 238, 238));
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let orange = ColorType::Rgb(255, 135, 0);
+        assert_eq!(orange.downgrade(ColorSupport::Truecolor), orange);
+        assert_eq!(
+            orange.downgrade(ColorSupport::Ansi256),
+            ColorType::Ansi256(208)
+        );
+        assert_eq!(
+            orange.downgrade(ColorSupport::Ansi16),
+            ColorType::Ansi("yellow".to_string())
+        );
+        // grays use the grayscale ramp
+        assert_eq!(
+            ColorType::Rgb(128, 128, 128).downgrade(ColorSupport::Ansi256),
+            ColorType::Ansi256(244)
+        );
+        assert_eq!(
+            ColorType::Ansi256(196).downgrade(ColorSupport::Ansi16),
+            ColorType::Ansi("bright red".to_string())
+        );
+        assert_eq!(
+            ColorType::Ansi256(196).downgrade(ColorSupport::Ansi256),
+            ColorType::Ansi256(196)
+        );
+        let red = ColorType::Ansi("red".to_string());
+        assert_eq!(red.downgrade(ColorSupport::Ansi16), red);
+    }
+
+    #[test]
+    fn test_css_names() {
+        assert_eq!(
+            ColorType::from_str("rebeccapurple"),
+            Ok(ColorType::Rgb(102, 51, 153))
+        );
+        assert_eq!(
+            ColorType::from_str("DarkOrange"),
+            Ok(ColorType::Rgb(255, 140, 0))
+        );
+        // ansi names take precedence over css names
+        assert_eq!(
+            ColorType::from_str("green"),
+            Ok(ColorType::Ansi("green".to_string()))
+        );
     }
 }
@@ -0,0 +1,187 @@
+use crate::timestamp::parse_duration;
+use chrono::Duration;
+use std::str::FromStr;
+
+pub(crate) const HELP: &str = "\
+commands:
+  q / quit / exit       quit
+  clear                 clear the screen
+  errors                only show errors
+  warnings              only show warnings
+  all                   show every line type again (--include/--exclude still apply)
+  p / pause             stop printing, new lines are kept until resume
+  r / resume            print the lines that came in while paused
+  resume summary        print a summary of those lines, plus their errors
+  mark [label]          print a timestamped marker line, pressing Enter on an empty line does the same
+  mark --no-summary [label]  the same, without the errors and warnings since the previous marker
+  stats                 show counts of lines, errors and warnings so far
+  /<text>               show earlier lines containing text, and highlight it from now on
+  /                     stop highlighting the search text
+  mute <code>           no notifications for this error code
+  mute <code> for 30m   quiet this error code for a while (s, m, h or d), like an error rule that expires
+  unmute <code>         undo mute
+  expand                print the repeats hidden by --dedup
+  help                  show this list";
+
+/// A command typed while watching the log
+#[derive(Debug, PartialEq)]
+pub(crate) enum Command {
+    Quit,
+    Clear,
+    Errors,
+    Warnings,
+    All,
+    Pause,
+    /// `summary` prints counts and errors instead of every pending line
+    Resume {
+        summary: bool,
+    },
+    /// print a marker line with an optional label, and the errors and warnings since the previous marker unless `summary` is false
+    Mark {
+        label: Option<String>,
+        summary: bool,
+    },
+    Stats,
+    /// an empty term clears the search
+    Search(String),
+    Mute(String),
+    /// add a rule quieting the code that expires after the duration
+    MuteFor(String, Duration),
+    Unmute(String),
+    /// print the repeats hidden by --dedup
+    Expand,
+    Help,
+}
+
+impl FromStr for Command {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(term) = s.trim_start().strip_prefix('/') {
+            return Ok(Command::Search(term.trim().to_string()));
+        }
+        if s.trim().is_empty() {
+            return Ok(Command::Mark {
+                label: None,
+                summary: true,
+            });
+        }
+        let mut words = s.split_whitespace();
+        let command = words.next().unwrap_or_default().to_lowercase();
+        let arg = words.next();
+        let code = |name: &str| match arg {
+            Some(code) if code.chars().all(|c| c.is_ascii_digit()) => Ok(code.to_string()),
+            Some(code) => Err(format!("{name}: expected an error code, got \"{code}\"")),
+            None => Err(format!(
+                "{name}: expected an error code, e.g. `{name} 3702`"
+            )),
+        };
+        match command.as_str() {
+            "q" | "quit" | "exit" => Ok(Command::Quit),
+            "clear" => Ok(Command::Clear),
+            "errors" => Ok(Command::Errors),
+            "warnings" => Ok(Command::Warnings),
+            "all" => Ok(Command::All),
+            "p" | "pause" => Ok(Command::Pause),
+            "r" | "resume" => match arg {
+                None => Ok(Command::Resume { summary: false }),
+                Some("summary") => Ok(Command::Resume { summary: true }),
+                Some(arg) => Err(format!(
+                    "resume: unknown option \"{arg}\", expected summary"
+                )),
+            },
+            "mark" => {
+                let rest = s
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .map(|(_, l)| l.trim())
+                    .unwrap_or_default();
+                let (rest, summary) = match rest.strip_prefix("--no-summary") {
+                    Some(label) if label.is_empty() || label.starts_with(char::is_whitespace) => {
+                        (label.trim(), false)
+                    }
+                    _ => (rest, true),
+                };
+                Ok(Command::Mark {
+                    label: (!rest.is_empty()).then(|| rest.to_string()),
+                    summary,
+                })
+            }
+            "stats" => Ok(Command::Stats),
+            "mute" => {
+                let code = code("mute")?;
+                match (words.next(), words.next(), words.next()) {
+                    (None, _, _) => Ok(Command::Mute(code)),
+                    (Some("for"), Some(duration), None) => Ok(Command::MuteFor(
+                        code,
+                        parse_duration(duration).map_err(|e| format!("mute: {e}"))?,
+                    )),
+                    _ => Err(format!(
+                        "mute: expected `mute {code}` or `mute {code} for 30m`"
+                    )),
+                }
+            }
+            "unmute" => Ok(Command::Unmute(code("unmute")?)),
+            "expand" => Ok(Command::Expand),
+            "help" | "?" => Ok(Command::Help),
+            _ => Err(format!(
+                "unknown command \"{}\", type help to see the commands",
+                s.trim()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Command::from_str("q"), Ok(Command::Quit));
+        assert_eq!(Command::from_str(" Errors "), Ok(Command::Errors));
+        assert_eq!(
+            Command::from_str("mute 3702"),
+            Ok(Command::Mute("3702".to_string()))
+        );
+        assert!(Command::from_str("mute").is_err());
+        assert!(Command::from_str("mute abc").is_err());
+        assert_eq!(
+            Command::from_str("mute 3702 for 1h30m"),
+            Ok(Command::MuteFor("3702".to_string(), Duration::minutes(90)))
+        );
+        assert!(Command::from_str("mute 3702 for").is_err());
+        assert!(Command::from_str("mute 3702 for ever").is_err());
+        assert!(Command::from_str("mute 3702 forever").is_err());
+        assert!(Command::from_str("mute 3702 for 30m please").is_err());
+        assert!(Command::from_str("mute 3702 for 30m 2h").is_err());
+        assert_eq!(
+            Command::from_str("resume summary"),
+            Ok(Command::Resume { summary: true })
+        );
+        assert!(Command::from_str("resume later").is_err());
+        assert_eq!(
+            Command::from_str("/My Script "),
+            Ok(Command::Search("My Script".to_string()))
+        );
+        assert_eq!(Command::from_str("/"), Ok(Command::Search(String::new())));
+        let mark = |label: Option<&str>, summary| Command::Mark {
+            label: label.map(String::from),
+            summary,
+        };
+        assert_eq!(Command::from_str(""), Ok(mark(None, true)));
+        assert_eq!(Command::from_str("mark"), Ok(mark(None, true)));
+        assert_eq!(
+            Command::from_str("mark  before  paste "),
+            Ok(mark(Some("before  paste"), true))
+        );
+        assert_eq!(
+            Command::from_str("mark --no-summary"),
+            Ok(mark(None, false))
+        );
+        assert_eq!(
+            Command::from_str("mark --no-summary before paste"),
+            Ok(mark(Some("before paste"), false))
+        );
+        assert!(Command::from_str("sing").is_err());
+    }
+}
@@ -1,18 +1,27 @@
-use crate::error_rule::{remove_no_match_rules, ErrorRule};
+use crate::color_support::ColorMode;
+use crate::dedup::check_window;
+use crate::error_rule::{deserialize_error_rules, ErrorRule};
+use crate::theme::is_builtin_theme;
+use crate::timestamp::{parse_duration, TimestampFormat};
 use crate::Args;
+use chrono::{DateTime, Local};
 use colored::Colorize;
+use regex::{Captures, Regex};
 use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 #[serde(default)]
 pub(crate) struct ConfigColor {
     pub(crate) foreground: String,
     pub(crate) background: String,
+    // None keeps the default attributes, an empty list clears them
+    pub(crate) attributes: Option<Vec<String>>,
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, Clone)]
 #[serde(default)]
 pub(crate) struct ConfigColorFields {
     pub(crate) timestamp: ConfigColor,
@@ -20,6 +29,85 @@ pub(crate) struct ConfigColorFields {
     pub(crate) error: ConfigColor,
     pub(crate) message: ConfigColor,
 }
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub(crate) struct ConfigSeparator {
+    pub(crate) text: String,
+    #[serde(flatten)]
+    pub(crate) color: ConfigColor,
+}
+
+/// per line type styles, layered on top of the built-in look
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub(crate) struct ConfigLineStyles {
+    pub(crate) success: ConfigColorFields,
+    pub(crate) warning: ConfigColorFields,
+    pub(crate) error: ConfigColorFields,
+    pub(crate) quiet_error: ConfigColorFields,
+    pub(crate) header: ConfigColorFields,
+    pub(crate) other: ConfigColor,
+    pub(crate) separator: ConfigSeparator,
+}
+
+/// a message highlight rule. Either a custom regex `pattern`, or the name of a `builtin` rule to restyle or disable
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub(crate) struct ConfigHighlight {
+    pub(crate) pattern: String,
+    pub(crate) builtin: String,
+    pub(crate) enabled: Option<bool>,
+    #[serde(flatten)]
+    pub(crate) color: ConfigColor,
+}
+
+// Layering lets a theme provide the base colors and the config override only the keys it sets.
+impl ConfigColor {
+    pub(crate) fn layer(&mut self, top: &ConfigColor) {
+        if !top.foreground.is_empty() {
+            self.foreground = top.foreground.clone();
+        }
+        if !top.background.is_empty() {
+            self.background = top.background.clone();
+        }
+        if top.attributes.is_some() {
+            self.attributes = top.attributes.clone();
+        }
+    }
+}
+
+impl ConfigColorFields {
+    pub(crate) fn layer(&mut self, top: &ConfigColorFields) {
+        self.timestamp.layer(&top.timestamp);
+        self.filename.layer(&top.filename);
+        self.error.layer(&top.error);
+        self.message.layer(&top.message);
+    }
+}
+
+impl ConfigLineStyles {
+    pub(crate) fn layer(&mut self, top: &ConfigLineStyles) {
+        self.success.layer(&top.success);
+        self.warning.layer(&top.warning);
+        self.error.layer(&top.error);
+        self.quiet_error.layer(&top.quiet_error);
+        self.header.layer(&top.header);
+        self.other.layer(&top.other);
+        if !top.separator.text.is_empty() {
+            self.separator.text = top.separator.text.clone();
+        }
+        self.separator.color.layer(&top.separator.color);
+    }
+}
+
+/// A named set of `--include`/`--exclude` patterns
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ConfigFilterSet {
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+}
+
 #[derive(Deserialize, Debug, Default)]
 #[serde(default)]
 pub(crate) struct Config {
@@ -27,13 +115,42 @@ pub(crate) struct Config {
     pub(crate) use_documents_directory: bool,
     pub(crate) errors_only: bool,
     pub(crate) warnings_only: bool,
+    pub(crate) before_context: Option<usize>,
+    pub(crate) after_context: Option<usize>,
+    pub(crate) context: Option<usize>,
+    pub(crate) operation_context: bool,
+    pub(crate) dedup: bool,
+    pub(crate) collapse_ok: bool,
+    pub(crate) explain_rules: bool,
+    /// seconds
+    #[serde(deserialize_with = "dedup_window_deserialize")]
+    pub(crate) dedup_window: Option<f64>,
+    pub(crate) history_size: Option<usize>,
+    pub(crate) timestamp_format: Option<TimestampFormat>,
     pub(crate) show_notifications: bool,
     pub(crate) beep: bool,
     pub(crate) beep_volume: f32,
     pub(crate) beep_path: String,
+    pub(crate) escalate_beep_path: String,
+    /// name of a built-in theme or path to a theme file. Relative paths are relative to the config file.
+    pub(crate) theme: String,
+    pub(crate) color_mode: Option<ColorMode>,
     pub(crate) colors: ConfigColorFields,
+    pub(crate) line_styles: ConfigLineStyles,
+    pub(crate) no_highlights: bool,
+    pub(crate) highlights: Vec<ConfigHighlight>,
+    /// `--include` patterns, always applied
+    pub(crate) include: Vec<String>,
+    /// `--exclude` patterns, always applied
+    pub(crate) exclude: Vec<String>,
+    /// `--where` query, used unless one is passed on the command line
+    #[serde(rename = "where")]
+    pub(crate) query: String,
+    /// named sets of patterns, chosen with `--filter-set`
+    pub(crate) filter_sets: HashMap<String, ConfigFilterSet>,
     #[serde(deserialize_with = "comma_list_deserialize")]
     pub(crate) quiet_errors: Vec<String>,
+    #[serde(alias = "line_rules", deserialize_with = "deserialize_error_rules")]
     pub(crate) error_rules: Vec<ErrorRule>,
 }
 
@@ -50,6 +167,16 @@ where
         .collect())
 }
 
+fn dedup_window_deserialize<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = f64::deserialize(deserializer)?;
+    check_window(secs)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
 pub(crate) fn get_default_config_path() -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
     Ok(dirs::config_dir()
         .ok_or("couldn't find config directory")?
@@ -83,7 +210,7 @@ pub(crate) fn get_config(config_path: Option<&str>) -> Result<Config, Box<dyn st
     };
 
     let mut buf = String::new();
-    File::open(config_path)
+    File::open(&config_path)
         .map_err(|e| format!("couln't open config file: {}", e))?
         .read_to_string(&mut buf)
         .map_err(|e| format!("couldn't read config file: {}", e))?;
@@ -91,11 +218,50 @@ pub(crate) fn get_config(config_path: Option<&str>) -> Result<Config, Box<dyn st
     let mut config: Config =
         json5::from_str(&buf).map_err(|e| format!("couldn't parse config file: {}", e))?;
 
-    remove_no_match_rules(&mut config.error_rules);
+    // an `expires` duration counts from the first time fmrl reads it. Write that down, so restarting fmrl or saving the config doesn't extend it
+    let now = Local::now();
+    let anchored = anchor_expiry_durations(&buf, now);
+    if anchored != buf {
+        if let Err(e) = std::fs::write(&config_path, anchored) {
+            println!(
+                "{}",
+                format!("WARNING: couldn't write when the expires durations start to the config file, they count from now: {e}")
+                    .black()
+                    .on_yellow()
+            );
+        }
+    }
+    for rule in &mut config.error_rules {
+        rule.resolve_expiry(now);
+    }
+
+    if !config.theme.is_empty() && !is_builtin_theme(&config.theme) {
+        let theme_path = std::path::Path::new(&config.theme);
+        if theme_path.is_relative() {
+            if let Some(config_dir) = config_path.parent() {
+                config.theme = config_dir.join(theme_path).to_string_lossy().to_string();
+            }
+        }
+    }
 
     Ok(config)
 }
 
+/// Give each `expires` duration in the config text a start, e.g. "8h" becomes "8h from 2026-10-18 09:00:00"
+fn anchor_expiry_durations(text: &str, now: DateTime<Local>) -> String {
+    let expires = Regex::new(r#"(\bexpires["']?\s*:\s*)(["'])([^"'\n]*)(["'])"#).unwrap();
+    expires
+        .replace_all(text, |caps: &Captures| {
+            let (key, open, value, close) = (&caps[1], &caps[2], &caps[3], &caps[4]);
+            if open != close || parse_duration(value).is_err() {
+                return caps[0].to_string();
+            }
+            let start = now.format("%Y-%m-%d %H:%M:%S");
+            format!("{key}{open}{} from {start}{close}", value.trim())
+        })
+        .into_owned()
+}
+
 pub(crate) fn update_args_from_config(args: &mut Args, config: &Config) {
     if config.errors_only {
         args.errors_only = true;
@@ -103,9 +269,45 @@ pub(crate) fn update_args_from_config(args: &mut Args, config: &Config) {
     if config.warnings_only {
         args.warnings_only = true;
     }
+    // any context option on the command line replaces the config's context options
+    if args.before_context.is_none() && args.after_context.is_none() && args.context.is_none() {
+        args.before_context = config.before_context;
+        args.after_context = config.after_context;
+        args.context = config.context;
+    }
+    if config.operation_context {
+        args.operation_context = true;
+    }
+    if config.dedup {
+        args.dedup = true;
+    }
+    if config.collapse_ok {
+        args.collapse_ok = true;
+    }
+    if config.explain_rules {
+        args.explain_rules = true;
+    }
+    if args.dedup_window.is_none() {
+        args.dedup_window = config.dedup_window;
+    }
+    if args.history_size.is_none() {
+        args.history_size = config.history_size;
+    }
     if config.show_separator {
         args.separator = true;
     }
+    if args.theme.is_none() && !config.theme.is_empty() {
+        args.theme = Some(config.theme.clone());
+    }
+    if args.color_mode.is_none() {
+        args.color_mode = config.color_mode;
+    }
+    if args.timestamp_format.is_none() {
+        args.timestamp_format = config.timestamp_format;
+    }
+    if config.no_highlights {
+        args.no_highlights = true;
+    }
     if config.show_notifications {
         args.notifications = true;
     }
@@ -118,6 +320,9 @@ pub(crate) fn update_args_from_config(args: &mut Args, config: &Config) {
     if !config.beep_path.is_empty() {
         args.beep_path = config.beep_path.clone();
     }
+    if !config.escalate_beep_path.is_empty() {
+        args.escalate_beep_path = config.escalate_beep_path.clone();
+    }
     if config.beep_volume > 0.0 {
         args.beep_volume = config.beep_volume;
     }
@@ -134,6 +339,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn quiet_errors_should_not_overwrite_non_empty() {
         let mut args = Args::default();
         args.quiet_errors = vec!["111".to_string()];
@@ -144,6 +350,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn quiet_errors_should_overwrite_empty() {
         let mut args = Args::default();
         args.quiet_errors = vec![];
@@ -154,6 +361,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn quiet_errors_empty_should_not_overwrite_non_empty() {
         let mut args = Args::default();
         args.quiet_errors = vec!["111".to_string()];
@@ -163,6 +371,105 @@ mod tests {
         assert_eq!(args.quiet_errors, vec!["111".to_string()]);
     }
 
+    #[test]
+    fn timestamp_format_arg_takes_precedence_over_config() {
+        let config = Config {
+            timestamp_format: Some(TimestampFormat::Delta),
+            ..Config::default()
+        };
+        let mut args = Args::default();
+        update_args_from_config(&mut args, &config);
+        assert_eq!(args.timestamp_format, Some(TimestampFormat::Delta));
+
+        let mut args = Args {
+            timestamp_format: Some(TimestampFormat::Time),
+            ..Args::default()
+        };
+        update_args_from_config(&mut args, &config);
+        assert_eq!(args.timestamp_format, Some(TimestampFormat::Time));
+
+        let config: Config = json5::from_str(r#"{ timestamp_format: "since-operation" }"#).unwrap();
+        assert_eq!(
+            config.timestamp_format,
+            Some(TimestampFormat::SinceOperation)
+        );
+    }
+
+    #[test]
+    fn layer_only_overrides_set_keys() {
+        let mut base: ConfigLineStyles = json5::from_str(
+            r#"{
+                error: { timestamp: { foreground: "white", background: "red", attributes: ["bold"] } },
+                separator: { text: "====", foreground: "yellow" },
+            }"#,
+        )
+        .unwrap();
+        let top: ConfigLineStyles = json5::from_str(
+            r#"{
+                error: { timestamp: { background: "magenta" } },
+                separator: { attributes: [] },
+            }"#,
+        )
+        .unwrap();
+        base.layer(&top);
+        assert_eq!(base.error.timestamp.foreground, "white");
+        assert_eq!(base.error.timestamp.background, "magenta");
+        assert_eq!(
+            base.error.timestamp.attributes,
+            Some(vec!["bold".to_string()])
+        );
+        assert_eq!(base.separator.text, "====");
+        assert_eq!(base.separator.color.foreground, "yellow");
+        assert_eq!(base.separator.color.attributes, Some(vec![]));
+    }
+
+    #[test]
+    fn theme_arg_takes_precedence_over_config() {
+        let config = Config {
+            theme: "light".to_string(),
+            ..Config::default()
+        };
+        let mut args = Args::default();
+        update_args_from_config(&mut args, &config);
+        assert_eq!(args.theme, Some("light".to_string()));
+
+        let mut args = Args {
+            theme: Some("monochrome".to_string()),
+            ..Args::default()
+        };
+        update_args_from_config(&mut args, &config);
+        assert_eq!(args.theme, Some("monochrome".to_string()));
+    }
+
+    #[test]
+    fn expiry_durations_get_a_start() {
+        let now = crate::timestamp::parse_local_datetime("2026-10-18 09:00").unwrap();
+        let text = r#"{ error_rules: [
+            { "error_code": 100, "expires": "8h", "action": "quiet" },
+            { error_code: 101, expires: '1h30m', action: "quiet" },
+            { "error_code": 102, "expires": "2026-10-19", "action": "quiet" },
+            { "error_code": 103, "expires": "8h from 2026-10-17 09:00", "action": "quiet" },
+            { "message_contains": "expires: \"8h\"", "action": "quiet" },
+        ] }"#;
+        assert_eq!(
+            anchor_expiry_durations(text, now),
+            text.replace(r#""8h""#, r#""8h from 2026-10-18 09:00:00""#)
+                .replace("'1h30m'", "'1h30m from 2026-10-18 09:00:00'")
+        );
+    }
+
+    #[test]
+    fn dedup_window_must_be_finite_and_not_negative() {
+        let config: Config = json5::from_str("{ dedup_window: 2.5 }").unwrap();
+        assert_eq!(config.dedup_window, Some(2.5));
+        for window in ["-1", "NaN", "Infinity"] {
+            assert!(json5::from_str::<Config>(&format!("{{ dedup_window: {window} }}")).is_err());
+        }
+        assert!(crate::parse_dedup_window("0").is_ok());
+        assert!(crate::parse_dedup_window("-0.5").is_err());
+        assert!(crate::parse_dedup_window("inf").is_err());
+    }
+
     #[test]
     fn test_comma_list_deserialize() {
         let my_struct = r#"
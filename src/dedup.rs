@@ -0,0 +1,259 @@
+use crate::line_processor::ProcessedLine;
+use crate::timestamp::parse_timestamp;
+use crate::LineType;
+use chrono::{DateTime, Duration, FixedOffset};
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
+
+/// How many of a group's repeats are kept for `expand`. The rest are only counted
+const MAX_SAMPLE: usize = 20;
+/// How many finished groups with repeats are kept for `expand`
+const MAX_FINISHED: usize = 100;
+
+/// Errors and warnings that repeat an earlier line, grouped under that line
+pub(crate) struct RepeatGroup {
+    id: usize,
+    code: String,
+    operation: Option<usize>,
+    last_seen: Option<DateTime<FixedOffset>>,
+    pub(crate) first: ProcessedLine,
+    /// the first few repeats
+    pub(crate) repeats: Vec<ProcessedLine>,
+    /// all repeats, including the ones not kept in `repeats`
+    repeat_count: usize,
+}
+
+impl RepeatGroup {
+    /// how many times the line occurred, including the first
+    pub(crate) fn count(&self) -> usize {
+        self.repeat_count + 1
+    }
+
+    pub(crate) fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+/// Detects errors and warnings that repeat an earlier one: same code and same message once GUIDs and numbers are normalized.
+/// Repeats count within the same import operation, or within a time window if one is given.
+/// Groups are dropped once their operation or window has passed, apart from the last few with repeats.
+pub(crate) struct Deduper {
+    window: Option<Duration>,
+    /// groups that can still get repeats, by code and normalized message
+    live: HashMap<(String, String), RepeatGroup>,
+    /// the latest groups that can't get repeats anymore but had some, oldest first
+    finished: VecDeque<RepeatGroup>,
+    next_id: usize,
+    guid: Regex,
+    number: Regex,
+}
+
+/// Checks a `--dedup-window` or `dedup_window` in seconds
+pub(crate) fn check_window(secs: f64) -> Result<f64, String> {
+    if secs.is_finite() && secs >= 0.0 {
+        Ok(secs)
+    } else {
+        Err(format!(
+            "dedup window must be a number of seconds, 0 or more, got {secs}"
+        ))
+    }
+}
+
+impl Deduper {
+    /// `window` is in seconds, see `check_window`
+    pub(crate) fn new(window: Option<f64>) -> Self {
+        Deduper {
+            window: window.map(|secs| Duration::milliseconds((secs * 1000.0) as i64)),
+            live: HashMap::new(),
+            finished: VecDeque::new(),
+            next_id: 0,
+            guid: Regex::new(
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+            )
+            .unwrap(),
+            number: Regex::new(r"\d+").unwrap(),
+        }
+    }
+
+    /// the message with GUIDs and numbers replaced by placeholders
+    fn normalize(&self, message: &str) -> String {
+        let message = self.guid.replace_all(message, "<guid>");
+        self.number.replace_all(&message, "#").into_owned()
+    }
+
+    /// whether a line in `operation` at `timestamp` would repeat `group`
+    fn is_live(
+        &self,
+        group: &RepeatGroup,
+        operation: Option<usize>,
+        timestamp: Option<DateTime<FixedOffset>>,
+    ) -> bool {
+        match self.window {
+            Some(window) => match (group.last_seen, timestamp) {
+                (Some(last), Some(now)) => now - last <= window,
+                _ => false,
+            },
+            None => group.operation == operation,
+        }
+    }
+
+    /// Move the groups a line in `operation` at `timestamp` can't repeat anymore out of `live`
+    fn evict(&mut self, operation: Option<usize>, timestamp: Option<DateTime<FixedOffset>>) {
+        let ended: Vec<(String, String)> = self
+            .live
+            .iter()
+            .filter(|(_, group)| !self.is_live(group, operation, timestamp))
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut ended: Vec<RepeatGroup> = ended
+            .iter()
+            .filter_map(|key| self.live.remove(key))
+            .filter(|group| group.repeat_count > 0)
+            .collect();
+        ended.sort_by_key(|group| group.id);
+        for group in ended {
+            if self.finished.len() == MAX_FINISHED {
+                self.finished.pop_front();
+            }
+            self.finished.push_back(group);
+        }
+    }
+
+    /// Returns the id of the group `line` was added to if it repeats an earlier line, otherwise starts a new group for it
+    pub(crate) fn check(&mut self, line: &ProcessedLine) -> Option<usize> {
+        let (LineType::Error(l) | LineType::Warning(l)) = &line.line else {
+            return None;
+        };
+        let key = (l.code.clone(), self.normalize(&l.message));
+        let timestamp = parse_timestamp(&line.raw_timestamp);
+        self.evict(line.operation, timestamp);
+        if let Some(group) = self.live.get_mut(&key) {
+            group.repeat_count += 1;
+            if group.repeats.len() < MAX_SAMPLE {
+                group.repeats.push(line.clone());
+            }
+            group.last_seen = timestamp.or(group.last_seen);
+            return Some(group.id);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let group = RepeatGroup {
+            id,
+            code: l.code.clone(),
+            operation: line.operation,
+            last_seen: timestamp,
+            first: line.clone(),
+            repeats: vec![],
+            repeat_count: 0,
+        };
+        self.live.insert(key, group);
+        None
+    }
+
+    /// the group with id `id`, unless it has been dropped
+    pub(crate) fn group(&self, id: usize) -> Option<&RepeatGroup> {
+        self.finished
+            .iter()
+            .chain(self.live.values())
+            .find(|g| g.id == id)
+    }
+
+    /// the groups with at least one repeat that are still kept, oldest first
+    pub(crate) fn repeated(&self) -> Vec<&RepeatGroup> {
+        let mut live: Vec<&RepeatGroup> =
+            self.live.values().filter(|g| g.repeat_count > 0).collect();
+        live.sort_by_key(|g| g.id);
+        self.finished.iter().chain(live).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_processor::LineProcessor;
+    use crate::Args;
+
+    const START: &str =
+        "2023-05-03 08:58:37.211 +0200\tmy.fmp12\t0\tImport of script steps from clipboard started";
+
+    fn line(time: &str, code: &str, message: &str) -> String {
+        format!("2023-05-03 {time} +0200\tMissing EDS::2::Open File\t{code}\t{message}")
+    }
+
+    #[test]
+    fn normalizes_guids_and_numbers() {
+        let d = Deduper::new(None);
+        assert_eq!(
+            d.normalize("File reference “1B1A4B0E-3E1C-4B8A-9B2C-0D5C1E2F3A4B” missing, 2 times"),
+            "File reference “<guid>” missing, # times"
+        );
+    }
+
+    #[test]
+    fn groups_repeats_within_an_operation() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut d = Deduper::new(None);
+        processor.process(START);
+        let first = processor.process(&line("08:58:37.212", "114", "Table 12 missing."));
+        assert_eq!(d.check(&first), None);
+        let repeat = processor.process(&line("08:58:37.213", "114", "Table 13 missing."));
+        assert_eq!(d.check(&repeat), Some(0));
+        // a different code isn't a repeat
+        let other = processor.process(&line("08:58:37.214", "106", "Table 13 missing."));
+        assert_eq!(d.check(&other), None);
+        assert_eq!(d.group(0).unwrap().count(), 2);
+
+        // a new operation starts over, and drops the groups of the old one that had no repeats
+        processor.process(START);
+        let next = processor.process(&line("08:58:38.000", "114", "Table 14 missing."));
+        assert_eq!(d.check(&next), None);
+        assert_eq!(d.live.len(), 1);
+        assert_eq!(d.finished.len(), 1);
+        assert_eq!(d.repeated().len(), 1);
+        assert!(d.group(1).is_none());
+    }
+
+    #[test]
+    fn groups_repeats_within_a_window() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut d = Deduper::new(Some(60.0));
+        let lines = [
+            "08:58:00.000",
+            "08:58:50.000",
+            "08:59:45.000",
+            "09:01:00.000",
+        ]
+        .map(|time| processor.process(&line(time, "3702", "Script 1 failed")));
+        assert_eq!(d.check(&lines[0]), None);
+        // the window slides with each repeat
+        assert_eq!(d.check(&lines[1]), Some(0));
+        assert_eq!(d.check(&lines[2]), Some(0));
+        assert_eq!(d.check(&lines[3]), None);
+        assert_eq!(d.live.len(), 1);
+    }
+
+    #[test]
+    fn keeps_a_sample_of_the_repeats() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut d = Deduper::new(None);
+        processor.process(START);
+        let repeat = processor.process(&line("08:58:37.212", "114", "Table 12 missing."));
+        for _ in 0..=MAX_SAMPLE + 5 {
+            d.check(&repeat);
+        }
+        let group = d.group(0).unwrap();
+        assert_eq!(group.count(), MAX_SAMPLE + 6);
+        assert_eq!(group.repeats.len(), MAX_SAMPLE);
+
+        // only the latest finished groups are kept
+        for i in 0..MAX_FINISHED + 1 {
+            processor.process(START);
+            let repeat = processor.process(&line("08:58:38.000", &(i + 1000).to_string(), "again"));
+            d.check(&repeat);
+            d.check(&repeat);
+        }
+        assert_eq!(d.finished.len(), MAX_FINISHED);
+        assert_eq!(d.repeated().len(), MAX_FINISHED + 1);
+        assert!(d.group(0).is_none());
+    }
+}
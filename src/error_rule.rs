@@ -1,17 +1,312 @@
-use crate::ImportLogLine;
+use crate::color_support::ColorSupport;
+use crate::config_file::ConfigColor;
+use crate::style::Style;
+use crate::timestamp::{parse_duration, parse_local_datetime};
+use crate::{ImportLogLine, LineType};
+use chrono::{DateTime, Duration, Local};
+use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
 
-#[derive(Default, Deserialize, Debug, PartialEq, Clone, Copy)]
+/// The kinds of line a rule applies to
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RuleLineType {
+    Error,
+    Warning,
+    Success,
+    Header,
+    Other,
+}
+
+/// What to do with an error line that matches a rule
+#[derive(Default, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum ErrorRuleAction {
+    /// still highlight the error, but don't notify
     #[default]
     Quiet,
+    /// don't notify or highlight
     Ignore,
+    /// always notify, even when quieted or muted, with its own sound
+    Escalate,
+    /// hide the line
+    Suppress,
+    /// color the timestamp, location and code columns
+    Highlight(HighlightStyle),
+    /// a short label printed after the message
+    Tag(String),
+}
+
+/// A highlight color, checked when the rule is loaded
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "ConfigColor")]
+pub(crate) struct HighlightStyle(Style);
+
+impl TryFrom<ConfigColor> for HighlightStyle {
+    type Error = String;
+    fn try_from(color: ConfigColor) -> Result<Self, Self::Error> {
+        Style::default()
+            .merge(&color, "highlight")
+            .map(HighlightStyle)
+    }
 }
 
+/// Everything the matching rules decided about a line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct RuleActions {
+    pub(crate) quiet: bool,
+    pub(crate) ignore: bool,
+    pub(crate) escalate: bool,
+    pub(crate) suppress: bool,
+    /// from the first matching rule that highlights
+    pub(crate) highlight: Option<Style>,
+    pub(crate) tags: Vec<String>,
+    /// positions of the matching rules in the list of rules
+    pub(crate) rules: Vec<usize>,
+    /// positions of the rules with a threshold whose condition matched. `Thresholds` decides whether they apply
+    pub(crate) counting: Vec<usize>,
+}
+
+impl RuleActions {
+    /// add the actions of the rule at position `i`
+    pub(crate) fn apply_rule(&mut self, i: usize, rule: &ErrorRule) {
+        self.rules.push(i);
+        for action in &rule.actions {
+            self.add(action);
+        }
+    }
+
+    fn add(&mut self, action: &ErrorRuleAction) {
+        match action {
+            ErrorRuleAction::Quiet => self.quiet = true,
+            ErrorRuleAction::Ignore => self.ignore = true,
+            ErrorRuleAction::Escalate => self.escalate = true,
+            ErrorRuleAction::Suppress => self.suppress = true,
+            ErrorRuleAction::Highlight(HighlightStyle(style)) => {
+                self.highlight.get_or_insert_with(|| style.clone());
+            }
+            ErrorRuleAction::Tag(tag) => {
+                if !self.tags.contains(tag) {
+                    self.tags.push(tag.clone());
+                }
+            }
+        }
+    }
+
+    /// whether any rule matched
+    pub(crate) fn is_some(&self) -> bool {
+        *self != RuleActions::default()
+    }
+
+    /// whether the rules turn off the line's usual notification
+    pub(crate) fn silences(&self) -> bool {
+        self.quiet || self.ignore
+    }
+
+    /// the tags as printed after the message, e.g. ` [migration] [legacy]`
+    pub(crate) fn tag_text(&self) -> String {
+        self.tags.iter().map(|tag| format!(" [{tag}]")).collect()
+    }
+
+    /// the action names, as used in the config and the query language
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        [
+            (self.quiet, "quiet"),
+            (self.ignore, "ignore"),
+            (self.escalate, "escalate"),
+            (self.suppress, "suppress"),
+            (self.highlight.is_some(), "highlight"),
+            (!self.tags.is_empty(), "tag"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawErrorRule")]
+pub(crate) struct ErrorRule {
+    name: Option<String>,
+    /// position in the config's list of rules, which can differ from the position in `Args::error_rules` once rules are removed. None for rules added while running
+    index: Option<usize>,
+    /// rules only apply to errors unless they say otherwise
+    line_types: Vec<RuleLineType>,
+    /// whether the config gave a line_type, which is enough for the rule to match every line of those types, errors included
+    line_type_given: bool,
+    condition: Condition,
+    actions: Vec<ErrorRuleAction>,
+    threshold: Option<Threshold>,
+    expires: Option<Expiry>,
+}
+
+/// When a rule stops applying
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expiry {
+    At(DateTime<Local>),
+    /// a duration without a start. See `ErrorRule::resolve_expiry`
+    After(Duration),
+}
+
+impl<'de> Deserialize<'de> for Expiry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let err = || {
+            serde::de::Error::custom(format!(
+                "expires: expected a date and time like \"2026-10-18 18:00\", a duration like \"8h\" or a duration and its start like \"8h from 2026-10-18 09:00\", got \"{s}\""
+            ))
+        };
+        let too_long = || serde::de::Error::custom(format!("expires: \"{s}\" is too long"));
+        if let Some(at) = parse_local_datetime(&s) {
+            return Ok(Expiry::At(at));
+        }
+        if let Some((duration, start)) = s.split_once(" from ") {
+            let duration = parse_duration(duration).map_err(|_| err())?;
+            let start = parse_local_datetime(start).ok_or_else(err)?;
+            return start
+                .checked_add_signed(duration)
+                .map(Expiry::At)
+                .ok_or_else(too_long);
+        }
+        let duration = parse_duration(&s).map_err(|_| err())?;
+        // so it can be resolved from now
+        Local::now()
+            .checked_add_signed(duration)
+            .ok_or_else(too_long)?;
+        Ok(Expiry::After(duration))
+    }
+}
+
+/// How many lines must match a rule before it applies
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawThreshold")]
+pub(crate) struct Threshold {
+    /// the rule applies to the line that brings the count to this, then counting starts over
+    pub(crate) count: usize,
+    /// only count lines this many seconds apart at most, by their timestamps
+    pub(crate) within: Option<f64>,
+    /// only count lines in the same import operation
+    pub(crate) per_operation: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawThreshold {
+    count: usize,
+    within: Option<f64>,
+    #[serde(default)]
+    per_operation: bool,
+}
+
+impl TryFrom<RawThreshold> for Threshold {
+    type Error = String;
+    fn try_from(raw: RawThreshold) -> Result<Self, Self::Error> {
+        if raw.count == 0 {
+            return Err("threshold count must be at least 1".to_string());
+        }
+        if raw.within.is_some_and(|secs| secs <= 0.0) {
+            return Err("threshold within must be a positive number of seconds".to_string());
+        }
+        Ok(Threshold {
+            count: raw.count,
+            within: raw.within,
+            per_operation: raw.per_operation,
+        })
+    }
+}
+
+impl Default for ErrorRule {
+    fn default() -> Self {
+        ErrorRule {
+            name: None,
+            index: None,
+            line_types: default_line_types(),
+            line_type_given: false,
+            condition: Condition::default(),
+            actions: vec![ErrorRuleAction::default()],
+            threshold: None,
+            expires: None,
+        }
+    }
+}
+
+fn default_line_types() -> Vec<RuleLineType> {
+    vec![RuleLineType::Error]
+}
+
+/// The shape of an error rule in the config: one or more actions plus the condition fields, side by side
+#[derive(Deserialize)]
+struct RawErrorRule {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(deserialize_with = "parse_one_or_many")]
+    action: Vec<ErrorRuleAction>,
+    #[serde(default, deserialize_with = "parse_some_one_or_many")]
+    line_type: Option<Vec<RuleLineType>>,
+    #[serde(default)]
+    threshold: Option<Threshold>,
+    #[serde(default)]
+    expires: Option<Expiry>,
+    #[serde(flatten)]
+    condition: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TryFrom<RawErrorRule> for ErrorRule {
+    type Error = serde_json::Error;
+    fn try_from(raw: RawErrorRule) -> Result<Self, Self::Error> {
+        Ok(ErrorRule {
+            name: raw.name,
+            // set by `deserialize_error_rules`
+            index: None,
+            line_type_given: raw.line_type.is_some(),
+            line_types: raw.line_type.unwrap_or_else(default_line_types),
+            condition: serde_json::from_value(serde_json::Value::Object(raw.condition))?,
+            actions: raw.action,
+            threshold: raw.threshold,
+            expires: raw.expires,
+        })
+    }
+}
+
+fn parse_some_one_or_many<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    parse_one_or_many(deserializer).map(Some)
+}
+
+/// a single value or a non-empty array of them
+fn parse_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    use serde::de::Error;
+
+    let value: serde_json::Value = Deserialize::deserialize(deserializer)?;
+    let values = match value {
+        serde_json::Value::Array(arr) => arr,
+        value => vec![value],
+    };
+    if values.is_empty() {
+        return Err(D::Error::custom("Expected a value or a non-empty array"));
+    }
+    values
+        .into_iter()
+        .map(|a| serde_json::from_value(a).map_err(D::Error::custom))
+        .collect()
+}
+
+/// What an error line must look like for a rule to apply
 #[derive(Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(deny_unknown_fields)]
-pub(crate) struct ErrorRule {
+pub(crate) struct Condition {
     #[serde(deserialize_with = "deserialize_error_code", default)]
     error_code: Option<String>,
     // rules act like an AND query clause. All rules must match for the rule to be satisfied, this lets you get specific about the shape of an error. e.g. starts with "foo" and contains "bar" and ends with "."
@@ -23,7 +318,59 @@ pub(crate) struct ErrorRule {
     location_contains: Vec<String>,
     location_starts_with: Option<String>,
     location_ends_with: Option<String>,
-    action: ErrorRuleAction,
+    message_matches: Option<RuleRegex>,
+    location_matches: Option<RuleRegex>,
+    // groups nest, and are ANDed with the fields above
+    /// at least one of these must match
+    #[serde(default)]
+    any_of: Vec<Condition>,
+    /// every one of these must match
+    #[serde(default)]
+    all_of: Vec<Condition>,
+    /// this must not match
+    not: Option<Box<Condition>>,
+}
+
+/// A regex compiled when the rule is loaded, so a bad pattern fails at startup instead of on the first error line
+#[derive(Debug, Clone)]
+pub(crate) struct RuleRegex(Regex);
+
+impl PartialEq for RuleRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl<'de> Deserialize<'de> for RuleRegex {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(RuleRegex)
+            .map_err(|e| serde::de::Error::custom(format!("invalid regex \"{pattern}\": {e}")))
+    }
+}
+
+/// Deserializes a list of rules one at a time, so errors say which rule is wrong
+pub(crate) fn deserialize_error_rules<'de, D>(deserializer: D) -> Result<Vec<ErrorRule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values: Vec<serde_json::Value> = Deserialize::deserialize(deserializer)?;
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            serde_json::from_value(value.clone())
+                .map(|rule| ErrorRule {
+                    index: Some(i),
+                    ..rule
+                })
+                .map_err(|e| serde::de::Error::custom(format!("error_rules[{i}] {value}: {e}")))
+        })
+        .collect()
 }
 
 // create fn parse_string_or_string_array
@@ -78,96 +425,365 @@ fn contains_only_digits(input: &str) -> bool {
     input.chars().all(|c| c.is_ascii_digit())
 }
 
-impl ErrorRule {
-    /// check if ImportLogLine matches this rule
-    fn get_action(&self, line: &ImportLogLine) -> Option<ErrorRuleAction> {
-        // we only care about error lines (non-zero error code)
-        if line.code == "0" {
-            return None;
-        };
-
+impl Condition {
+    fn is_match(&self, line: &ImportLogLine) -> bool {
         if let Some(error_code) = &self.error_code {
             if error_code != &line.code {
-                return None;
+                return false;
             }
         }
 
         for msg in &self.message_contains {
             if !line.message.contains(msg) {
-                return None;
+                return false;
             }
         }
 
         if let Some(message_starts_with) = &self.message_starts_with {
             if !line.message.starts_with(message_starts_with) {
-                return None;
+                return false;
             }
         }
 
         if let Some(message_ends_with) = &self.message_ends_with {
             if !line.message.ends_with(message_ends_with) {
-                return None;
+                return false;
             }
         }
 
         for msg in &self.location_contains {
             if !line.filename.contains(msg) {
-                return None;
+                return false;
             }
         }
 
         if let Some(location_starts_with) = &self.location_starts_with {
             if !line.filename.starts_with(location_starts_with) {
-                return None;
+                return false;
             }
         }
 
         if let Some(location_ends_with) = &self.location_ends_with {
             if !line.filename.ends_with(location_ends_with) {
-                return None;
+                return false;
+            }
+        }
+
+        if let Some(RuleRegex(regex)) = &self.message_matches {
+            if !regex.is_match(&line.message) {
+                return false;
+            }
+        }
+
+        if let Some(RuleRegex(regex)) = &self.location_matches {
+            if !regex.is_match(&line.filename) {
+                return false;
+            }
+        }
+
+        // an empty any_of doesn't restrict anything, like the other fields when they're unset
+        if !self.any_of.is_empty() && !self.any_of.iter().any(|c| c.is_match(line)) {
+            return false;
+        }
+
+        if !self.all_of.iter().all(|c| c.is_match(line)) {
+            return false;
+        }
+
+        if let Some(not) = &self.not {
+            if not.is_match(line) {
+                return false;
             }
         }
 
-        // return the action if all the rules match
-        Some(self.action)
+        true
     }
 
-    /// check if only the action field is set on this rule. If so, then the rule has no match logic so will never be used and can be ignored.
-    fn no_match_logic(&self) -> bool {
-        // set the non-optional fields to the same on boths sides of the comparison, then compare against the default ErrorRule. default Options are None, so if they match, we can assume all optional fields are None
-        let action = ErrorRuleAction::default();
-        let default = ErrorRule {
-            action,
-            ..ErrorRule::default()
+    /// Whether every line that matches `self` also matches `other`. This only compares the fields, so `false` can also mean it can't tell.
+    fn implies(&self, other: &Condition) -> bool {
+        if *other == Condition::default() || self.all_of.iter().any(|c| c.implies(other)) {
+            return true;
+        }
+        // text the message or location is known to contain
+        let messages = || {
+            self.message_contains
+                .iter()
+                .chain(&self.message_starts_with)
+                .chain(&self.message_ends_with)
+        };
+        let locations = || {
+            self.location_contains
+                .iter()
+                .chain(&self.location_starts_with)
+                .chain(&self.location_ends_with)
+        };
+        let narrower = |ours: &Option<String>,
+                        theirs: &Option<String>,
+                        f: fn(&str, &str) -> bool| match (ours, theirs) {
+            (_, None) => true,
+            (Some(ours), Some(theirs)) => f(ours, theirs),
+            (None, Some(_)) => false,
         };
 
-        let this_rule = ErrorRule {
-            action,
-            ..self.clone()
+        (other.error_code.is_none() || self.error_code == other.error_code)
+            && other
+                .message_contains
+                .iter()
+                .all(|s| messages().any(|m| m.contains(s.as_str())))
+            && narrower(&self.message_starts_with, &other.message_starts_with, |a, b| a.starts_with(b))
+            && narrower(&self.message_ends_with, &other.message_ends_with, |a, b| a.ends_with(b))
+            && other
+                .location_contains
+                .iter()
+                .all(|s| locations().any(|l| l.contains(s.as_str())))
+            && narrower(&self.location_starts_with, &other.location_starts_with, |a, b| a.starts_with(b))
+            && narrower(&self.location_ends_with, &other.location_ends_with, |a, b| a.ends_with(b))
+            && (other.message_matches.is_none() || self.message_matches == other.message_matches)
+            && (other.location_matches.is_none() || self.location_matches == other.location_matches)
+            && (other.any_of.is_empty()
+                || other.any_of.iter().any(|c| self.implies(c))
+                || (!self.any_of.is_empty()
+                    && self
+                        .any_of
+                        .iter()
+                        .all(|ours| other.any_of.iter().any(|c| ours.implies(c)))))
+            && other.all_of.iter().all(|c| self.implies(c))
+            // lines without what `ours` excludes are also without anything narrower than it
+            && other.not.as_ref().is_none_or(|theirs| {
+                self.not.as_ref().is_some_and(|ours| theirs.implies(ours))
+            })
+    }
+}
+
+impl ErrorRule {
+    /// check if ImportLogLine matches this rule
+    fn get_action(
+        &self,
+        line_type: RuleLineType,
+        line: &ImportLogLine,
+    ) -> Option<&[ErrorRuleAction]> {
+        if !self.line_types.contains(&line_type) {
+            return None;
         };
 
-        default == this_rule
+        // return the actions if all the rules match
+        self.condition
+            .is_match(line)
+            .then_some(self.actions.as_slice())
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// position in the config's list of rules
+    pub(crate) fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// how --explain-rules refers to the rule, e.g. `error_rules[3] "noisy 114s"`
+    pub(crate) fn label(&self) -> String {
+        match (self.index, &self.name) {
+            (Some(i), Some(name)) => format!("error_rules[{i}] \"{name}\""),
+            (Some(i), None) => format!("error_rules[{i}]"),
+            // added while running, e.g. `mute 114`
+            (None, Some(name)) => name.clone(),
+            (None, None) => "added rule".to_string(),
+        }
+    }
+
+    /// A rule added by `mute <code> for <duration>`: quiet the code until `until`
+    pub(crate) fn mute(code: &str, until: DateTime<Local>) -> Self {
+        ErrorRule {
+            name: Some(format!("mute {code}")),
+            index: None,
+            condition: Condition {
+                error_code: Some(code.to_string()),
+                ..Condition::default()
+            },
+            expires: Some(Expiry::At(until)),
+            ..ErrorRule::default()
+        }
+    }
+
+    /// Expire the rule now if `mute` made it for `code`. Returns whether it did
+    pub(crate) fn end_mute(&mut self, code: &str, now: DateTime<Local>) -> bool {
+        let is_mute = self.index.is_none()
+            && self.condition.error_code.as_deref() == Some(code)
+            && !self.expired(now);
+        if is_mute {
+            self.expires = Some(Expiry::At(now));
+        }
+        is_mute
+    }
+
+    /// Turn an `expires` duration into a time, counting from `since`
+    pub(crate) fn resolve_expiry(&mut self, since: DateTime<Local>) {
+        if let Some(Expiry::After(duration)) = self.expires {
+            // a duration too long to add never expires
+            self.expires = since.checked_add_signed(duration).map(Expiry::At);
+        }
+    }
+
+    /// when the rule expires, once `resolve_expiry` has been called
+    pub(crate) fn expires_at(&self) -> Option<DateTime<Local>> {
+        match self.expires {
+            Some(Expiry::At(at)) => Some(at),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn expired(&self, now: DateTime<Local>) -> bool {
+        self.expires_at().is_some_and(|at| at <= now)
+    }
+
+    /// map highlight colors to the nearest ones the terminal supports
+    pub(crate) fn downgrade(&mut self, support: ColorSupport) {
+        for action in &mut self.actions {
+            if let ErrorRuleAction::Highlight(HighlightStyle(style)) = action {
+                *style = style.downgrade(support);
+            }
+        }
+    }
+
+    /// check if only the action field is set on this rule. If so, then the rule has no match logic so will never be used and can be ignored.
+    /// A rule with a line_type can match every line of that type, e.g. to quiet all warnings.
+    pub(crate) fn no_match_logic(&self) -> bool {
+        self.condition == Condition::default() && !self.line_type_given && self.threshold.is_none()
+    }
+
+    pub(crate) fn threshold(&self) -> Option<&Threshold> {
+        self.threshold.as_ref()
+    }
+
+    /// whether this rule matches the same lines and does the same things as `other`
+    pub(crate) fn same_as(&self, other: &ErrorRule) -> bool {
+        self.line_types == other.line_types
+            && self.condition == other.condition
+            && self.actions == other.actions
+            && self.threshold == other.threshold
+            && self.expires == other.expires
+    }
+
+    /// Whether this rule matches every line `later` does and already does everything `later` would, so `later` has no effect
+    pub(crate) fn shadows(&self, later: &ErrorRule) -> bool {
+        let has = |action: &ErrorRuleAction| self.actions.contains(action);
+        // a rule with a threshold only applies to some of the lines it matches
+        self.threshold.is_none()
+            // and one that expires first stops shadowing
+            && match (self.expires_at(), later.expires_at()) {
+                (None, _) => true,
+                (Some(ours), Some(theirs)) => ours >= theirs,
+                (Some(_), None) => false,
+            }
+            && later.line_types.iter().all(|t| self.line_types.contains(t))
+            && later.condition.implies(&self.condition)
+            && later.actions.iter().all(|action| match action {
+                // ignore silences too
+                ErrorRuleAction::Quiet => has(action) || has(&ErrorRuleAction::Ignore),
+                // the first highlight wins
+                ErrorRuleAction::Highlight(_) => self
+                    .actions
+                    .iter()
+                    .any(|a| matches!(a, ErrorRuleAction::Highlight(_))),
+                _ => has(action),
+            })
+    }
+
+    /// why this rule can never match, or its actions work against each other
+    pub(crate) fn contradiction(&self) -> Option<String> {
+        let has = |action: &ErrorRuleAction| self.actions.contains(action);
+        if let Some(code) = &self.condition.error_code {
+            // error lines have a non-zero code, warning and success lines 0, header and other lines no number
+            let possible = if code == "0" {
+                self.line_types
+                    .iter()
+                    .any(|t| matches!(t, RuleLineType::Warning | RuleLineType::Success))
+            } else {
+                self.line_types.contains(&RuleLineType::Error)
+            };
+            if !possible {
+                return Some(format!(
+                    "error_code {code} never occurs on {} lines",
+                    self.line_type_names()
+                ));
+            }
+        }
+        if let Some(not) = &self.condition.not {
+            let without_not = Condition {
+                not: None,
+                ..self.condition.clone()
+            };
+            if without_not.implies(not) {
+                return Some("its `not` excludes every line the rest of it matches".to_string());
+            }
+        }
+        if has(&ErrorRuleAction::Ignore) && has(&ErrorRuleAction::Escalate) {
+            return Some("it both ignores and escalates".to_string());
+        }
+        if has(&ErrorRuleAction::Suppress) {
+            if let Some(action) = self
+                .actions
+                .iter()
+                .find(|a| matches!(a, ErrorRuleAction::Highlight(_) | ErrorRuleAction::Tag(_)))
+            {
+                let name = match action {
+                    ErrorRuleAction::Highlight(_) => "highlight",
+                    _ => "tag",
+                };
+                return Some(format!("it suppresses the lines it would {name}"));
+            }
+        }
+        None
+    }
+
+    fn line_type_names(&self) -> String {
+        self.line_types
+            .iter()
+            .map(|t| format!("{t:?}").to_lowercase())
+            .collect::<Vec<_>>()
+            .join("/")
     }
 }
 
-pub(crate) fn apply_error_rules(
-    rules: &[ErrorRule],
-    line: &ImportLogLine,
-) -> Option<ErrorRuleAction> {
-    use ErrorRuleAction::*;
-    let mut action = None;
-    for rule in rules {
-        match rule.get_action(line) {
-            Some(Quiet) => action = Some(Quiet),
-            Some(Ignore) => return Some(Ignore),
-            None => continue,
+/// combine the actions of every rule that matches the line
+pub(crate) fn apply_error_rules(rules: &[ErrorRule], line: &LineType) -> RuleActions {
+    let (line_type, line) = match line {
+        LineType::Error(l) => (RuleLineType::Error, Cow::Borrowed(l)),
+        LineType::Warning(l) => (RuleLineType::Warning, Cow::Borrowed(l)),
+        LineType::Success(l) => (RuleLineType::Success, Cow::Borrowed(l)),
+        LineType::Header(l) => (RuleLineType::Header, Cow::Borrowed(l)),
+        // lines that aren't log entries are all message
+        LineType::Other(text) => (
+            RuleLineType::Other,
+            Cow::Owned(ImportLogLine {
+                message: text.clone(),
+                ..ImportLogLine::default()
+            }),
+        ),
+    };
+    let mut actions = RuleActions::default();
+    let now = Local::now();
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.expired(now) || rule.get_action(line_type, &line).is_none() {
+            continue;
+        }
+        if rule.threshold.is_some() {
+            actions.counting.push(i);
+        } else {
+            actions.apply_rule(i, rule);
         }
     }
-    action
+    actions
 }
 
-pub(crate) fn remove_no_match_rules(rules: &mut Vec<ErrorRule>) {
-    rules.retain(|rule| !rule.no_match_logic());
+/// Remove rules without conditions, which would otherwise match every error. Returns them.
+pub(crate) fn remove_no_match_rules(rules: &mut Vec<ErrorRule>) -> Vec<ErrorRule> {
+    let (removed, kept) = std::mem::take(rules)
+        .into_iter()
+        .partition(ErrorRule::no_match_logic);
+    *rules = kept;
+    removed
 }
 
 #[cfg(test)]
@@ -178,27 +794,27 @@ mod tests {
     fn deserialize_error_rule() {
         let json = r#"{"error_code": "123", "message_contains": "abc", "action": "quiet"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, Some("123".to_string()));
-        assert_eq!(res.message_contains, vec!["abc".to_string()]);
-        assert_eq!(res.action, ErrorRuleAction::Quiet);
+        assert_eq!(res.condition.error_code, Some("123".to_string()));
+        assert_eq!(res.condition.message_contains, vec!["abc".to_string()]);
+        assert_eq!(res.actions, [ErrorRuleAction::Quiet]);
 
         let json = r#"{"message_contains": "abc", "action": "ignore"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, None);
-        assert_eq!(res.message_contains, vec!["abc".to_string()]);
-        assert_eq!(res.action, ErrorRuleAction::Ignore);
+        assert_eq!(res.condition.error_code, None);
+        assert_eq!(res.condition.message_contains, vec!["abc".to_string()]);
+        assert_eq!(res.actions, [ErrorRuleAction::Ignore]);
 
         let json = r#"{"error_code": "123", "action": "ignore"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, Some("123".to_string()));
-        assert_eq!(res.message_contains, Vec::<String>::new());
-        assert_eq!(res.action, ErrorRuleAction::Ignore);
+        assert_eq!(res.condition.error_code, Some("123".to_string()));
+        assert_eq!(res.condition.message_contains, Vec::<String>::new());
+        assert_eq!(res.actions, [ErrorRuleAction::Ignore]);
 
         let json = r#"{"action": "ignore"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, None);
-        assert_eq!(res.message_contains, Vec::<String>::new());
-        assert_eq!(res.action, ErrorRuleAction::Ignore);
+        assert_eq!(res.condition.error_code, None);
+        assert_eq!(res.condition.message_contains, Vec::<String>::new());
+        assert_eq!(res.actions, [ErrorRuleAction::Ignore]);
 
         // only action:
         let json = r#"{"action": "ignore"}"#;
@@ -213,32 +829,73 @@ mod tests {
             "message_ends_with": "def",
             "location_contains": "ghi",
             "location_starts_with": "jkl",
-            "location_ends_with": "mno"
+            "location_ends_with": "mno",
+            "message_matches": "^abc",
+            "location_matches": "o$"
         }"#;
         serde_json::from_str::<ErrorRule>(json).unwrap();
     }
 
+    #[test]
+    fn get_action_matches_works() {
+        let rule: ErrorRule = serde_json::from_str(
+            r#"{"action": "quiet", "message_matches": "^File reference “[0-9A-F-]+” missing", "location_matches": "Open File$"}"#,
+        )
+        .unwrap();
+        let mut line = ImportLogLine {
+            code: "114".to_string(),
+            filename: "Missing EDS::2::Open File".to_string(),
+            message: "File reference “DA9FD4AF-B5E2-41CE-807F-ECA0A9CF44C1” missing.".to_string(),
+            ..ImportLogLine::default()
+        };
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Quiet][..])
+        );
+        line.message = "File reference “WillBeReused” missing.".to_string();
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
+    }
+
+    #[test]
+    fn invalid_regex_error_points_at_the_rule() {
+        #[derive(Deserialize)]
+        struct Rules {
+            #[serde(deserialize_with = "deserialize_error_rules")]
+            error_rules: Vec<ErrorRule>,
+        }
+        let json = r#"{"error_rules": [
+            {"error_code": 114, "action": "quiet"},
+            {"message_matches": "(unclosed", "action": "quiet"}
+        ]}"#;
+        let err = json5::from_str::<Rules>(json)
+            .map(|r| r.error_rules)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("error_rules[1]"), "{err}");
+        assert!(err.contains("invalid regex \"(unclosed\""), "{err}");
+    }
+
     #[test]
     fn deserialize_message_contains_should_allow_string_or_array() {
         // string
         let json = r#"{"action": "quiet", "message_contains": "abc"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.message_contains, vec!["abc".to_string()]);
+        assert_eq!(res.condition.message_contains, vec!["abc".to_string()]);
         // array
         let json = r#"{"action": "quiet", "message_contains": ["abc", "def"]}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
         assert_eq!(
-            res.message_contains,
+            res.condition.message_contains,
             vec!["abc".to_string(), "def".to_string()]
         );
         // empty array
         let json = r#"{"action": "quiet", "message_contains": []}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.message_contains, Vec::<String>::new());
+        assert_eq!(res.condition.message_contains, Vec::<String>::new());
         // undefined becomes empty array
         let json = r#"{"action": "quiet"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.message_contains, Vec::<String>::new());
+        assert_eq!(res.condition.message_contains, Vec::<String>::new());
     }
 
     #[test]
@@ -246,40 +903,40 @@ mod tests {
         // string
         let json = r#"{"action": "quiet", "location_contains": "abc"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.location_contains, vec!["abc".to_string()]);
+        assert_eq!(res.condition.location_contains, vec!["abc".to_string()]);
         // array
         let json = r#"{"action": "quiet", "location_contains": ["abc", "def"]}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
         assert_eq!(
-            res.location_contains,
+            res.condition.location_contains,
             vec!["abc".to_string(), "def".to_string()]
         );
         // empty array
         let json = r#"{"action": "quiet", "location_contains": []}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.location_contains, Vec::<String>::new());
+        assert_eq!(res.condition.location_contains, Vec::<String>::new());
         // undefined becomes empty array
         let json = r#"{"action": "quiet"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.location_contains, Vec::<String>::new());
+        assert_eq!(res.condition.location_contains, Vec::<String>::new());
     }
 
     #[test]
     fn error_code_can_be_num_or_string() {
         let json = r#"{"action": "quiet", "error_code": "123"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, Some("123".to_string()));
+        assert_eq!(res.condition.error_code, Some("123".to_string()));
 
         let json = r#"{"action": "quiet", "error_code": 123}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, Some("123".to_string()));
+        assert_eq!(res.condition.error_code, Some("123".to_string()));
     }
 
     #[test]
     fn empty_error_code_deserializes_to_none() {
         let json = r#"{"action": "quiet", "error_code": ""}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.error_code, None);
+        assert_eq!(res.condition.error_code, None);
     }
 
     #[test]
@@ -302,11 +959,11 @@ mod tests {
     fn deserialize_error_rule_action() {
         let json = r#"{"action": "quiet"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.action, ErrorRuleAction::Quiet);
+        assert_eq!(res.actions, [ErrorRuleAction::Quiet]);
 
         let json = r#"{"action": "ignore"}"#;
         let res: ErrorRule = serde_json::from_str(json).unwrap();
-        assert_eq!(res.action, ErrorRuleAction::Ignore);
+        assert_eq!(res.actions, [ErrorRuleAction::Ignore]);
 
         let json = r#"{ "action": "INVALID_VARIANT"}"#;
         let res: std::result::Result<ErrorRule, _> = serde_json::from_str(json);
@@ -327,10 +984,13 @@ mod tests {
     fn get_action_works() {
         // TODO: test each of the match logic fields individually
         let rule = ErrorRule {
-            error_code: Some("123".to_string()),
-            message_contains: vec!["abc".to_string()],
-            action: ErrorRuleAction::Quiet,
-            location_contains: vec![],
+            condition: Condition {
+                error_code: Some("123".to_string()),
+                message_contains: vec!["abc".to_string()],
+                location_contains: vec![],
+                ..Condition::default()
+            },
+            actions: vec![ErrorRuleAction::Quiet],
             ..ErrorRule::default()
         };
 
@@ -339,94 +999,124 @@ mod tests {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::Quiet));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Quiet][..])
+        );
 
         let line = ImportLogLine {
             code: "123".to_string(),
             message: "does not match".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
     }
 
     #[test]
     fn get_action_message_contains_works() {
         // one element
         let rule = ErrorRule {
-            message_contains: vec!["abc".to_string()],
+            condition: Condition {
+                message_contains: vec!["abc".to_string()],
+                ..Condition::default()
+            },
             ..ErrorRule::default()
         };
         let line = ImportLogLine {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::default()));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::default()][..])
+        );
 
         let line = ImportLogLine {
             message: "HELLO_def_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
 
         // two elements. line.message must contain both elements to match
         let rule = ErrorRule {
-            action: ErrorRuleAction::Quiet,
-            message_contains: vec!["abc".to_string(), "def".to_string()],
+            condition: Condition {
+                message_contains: vec!["abc".to_string(), "def".to_string()],
+                ..Condition::default()
+            },
+            actions: vec![ErrorRuleAction::Quiet],
             ..ErrorRule::default()
         };
         let mut line = ImportLogLine {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
         line.message = "HELLO_def_WORLD".to_string();
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
         line.message = "HELLO_abc_def_WORLD".to_string();
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::default()));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::default()][..])
+        );
     }
 
     #[test]
     fn get_action_location_contains_works() {
         // one element
         let rule = ErrorRule {
-            location_contains: vec!["abc".to_string()],
+            condition: Condition {
+                location_contains: vec!["abc".to_string()],
+                ..Condition::default()
+            },
             ..ErrorRule::default()
         };
         let line = ImportLogLine {
             filename: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::default()));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::default()][..])
+        );
 
         let line = ImportLogLine {
             filename: "HELLO_def_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
 
         // two elements. line.filename must contain both elements to match
         let rule = ErrorRule {
-            action: ErrorRuleAction::Quiet,
-            location_contains: vec!["abc".to_string(), "def".to_string()],
+            condition: Condition {
+                location_contains: vec!["abc".to_string(), "def".to_string()],
+                ..Condition::default()
+            },
+            actions: vec![ErrorRuleAction::Quiet],
             ..ErrorRule::default()
         };
         let mut line = ImportLogLine {
             filename: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
         line.filename = "HELLO_def_WORLD".to_string();
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
         line.filename = "HELLO_abc_def_WORLD".to_string();
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::default()));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::default()][..])
+        );
     }
 
     #[test]
     fn get_action_matches_any_error_if_error_code_is_none() {
         let rule = ErrorRule {
-            error_code: None,
-            message_contains: vec!["abc".to_string()],
-            action: ErrorRuleAction::Quiet,
+            condition: Condition {
+                error_code: None,
+                message_contains: vec!["abc".to_string()],
+                ..Condition::default()
+            },
+            actions: vec![ErrorRuleAction::Quiet],
             ..ErrorRule::default()
         };
 
@@ -435,23 +1125,32 @@ mod tests {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::Quiet));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Quiet][..])
+        );
 
         let line = ImportLogLine {
             code: "456".to_string(),
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), Some(ErrorRuleAction::Quiet));
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Quiet][..])
+        );
     }
 
     #[test]
     fn get_action_returns_none_for_non_error_lines() {
         let rule = ErrorRule {
-            error_code: Some("123".to_string()),
-            message_contains: vec!["abc".to_string()],
-            // location_contains: None,
-            action: ErrorRuleAction::Quiet,
+            condition: Condition {
+                error_code: Some("123".to_string()),
+                message_contains: vec!["abc".to_string()],
+                // location_contains: None,
+                ..Condition::default()
+            },
+            actions: vec![ErrorRuleAction::Quiet],
             ..ErrorRule::default()
         };
         let line = ImportLogLine {
@@ -459,22 +1158,28 @@ mod tests {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        assert_eq!(rule.get_action(&line), None);
+        assert_eq!(rule.get_action(RuleLineType::Success, &line), None);
     }
 
     #[test]
     fn apply_error_rules_works() {
         let rules = vec![
             ErrorRule {
-                error_code: Some("123".to_string()),
-                message_contains: vec!["abc".to_string()],
-                action: ErrorRuleAction::Quiet,
+                condition: Condition {
+                    error_code: Some("123".to_string()),
+                    message_contains: vec!["abc".to_string()],
+                    ..Condition::default()
+                },
+                actions: vec![ErrorRuleAction::Quiet],
                 ..ErrorRule::default()
             },
             ErrorRule {
-                error_code: Some("456".to_string()),
-                message_contains: vec!["def".to_string()],
-                action: ErrorRuleAction::Ignore,
+                condition: Condition {
+                    error_code: Some("456".to_string()),
+                    message_contains: vec!["def".to_string()],
+                    ..Condition::default()
+                },
+                actions: vec![ErrorRuleAction::Ignore],
                 ..ErrorRule::default()
             },
         ];
@@ -484,16 +1189,16 @@ mod tests {
             message: "HELLO_abc_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        let res = apply_error_rules(&rules, &line);
-        assert_eq!(res, Some(ErrorRuleAction::Quiet));
+        let res = apply_error_rules(&rules, &LineType::Error(line));
+        assert!(res.quiet && !res.ignore);
 
         let line = ImportLogLine {
             code: "456".to_string(),
             message: "HELLO_def_WORLD".to_string(),
             ..ImportLogLine::default()
         };
-        let res = apply_error_rules(&rules, &line);
-        assert_eq!(res, Some(ErrorRuleAction::Ignore));
+        let res = apply_error_rules(&rules, &LineType::Error(line));
+        assert!(res.ignore && !res.quiet);
     }
 
     #[test]
@@ -502,10 +1207,22 @@ mod tests {
         assert!(rule.no_match_logic());
 
         let rule = ErrorRule {
-            error_code: Some("123".to_string()),
+            condition: Condition {
+                error_code: Some("123".to_string()),
+                ..Condition::default()
+            },
             ..ErrorRule::default()
         };
         assert!(!rule.no_match_logic());
+
+        // a line_type is enough, even the default one
+        for json in [
+            r#"{ "line_type": "warning", "action": "quiet" }"#,
+            r#"{ "line_type": "error", "action": "quiet" }"#,
+        ] {
+            let rule: ErrorRule = json5::from_str(json).unwrap();
+            assert!(!rule.no_match_logic(), "{json}");
+        }
     }
 
     #[test]
@@ -521,4 +1238,242 @@ mod tests {
         let res = serde_json::from_str::<Vec<ErrorRule>>(json);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn condition_groups_work() {
+        let json = r#"{
+            "any_of": [{ "error_code": 114 }, { "error_code": 100 }],
+            "not": { "location_contains": "Production" },
+            "action": "ignore"
+        }"#;
+        let rule: ErrorRule = json5::from_str(json).unwrap();
+        let mut line = ImportLogLine {
+            code: "114".to_string(),
+            filename: "Missing EDS::2::Open File".to_string(),
+            ..ImportLogLine::default()
+        };
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Ignore][..])
+        );
+        line.code = "100".to_string();
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Ignore][..])
+        );
+        line.code = "3702".to_string();
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
+        line.code = "114".to_string();
+        line.filename = "Production::Open File".to_string();
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
+
+        // groups nest, and are ANDed with flat fields
+        let json = r#"{
+            "message_contains": "missing",
+            "all_of": [{ "any_of": [{ "error_code": 1 }, { "location_ends_with": "File" }] }],
+            "action": "quiet"
+        }"#;
+        let rule: ErrorRule = json5::from_str(json).unwrap();
+        let mut line = ImportLogLine {
+            code: "114".to_string(),
+            filename: "Missing EDS::2::Open File".to_string(),
+            message: "File reference missing.".to_string(),
+            ..ImportLogLine::default()
+        };
+        assert_eq!(
+            rule.get_action(RuleLineType::Error, &line),
+            Some(&[ErrorRuleAction::Quiet][..])
+        );
+        line.message = "something else".to_string();
+        assert_eq!(rule.get_action(RuleLineType::Error, &line), None);
+
+        // unknown fields fail inside groups too, and groups can't have actions
+        let json = r#"{ "any_of": [{ "error_code": 1, "action": "quiet" }], "action": "quiet" }"#;
+        assert!(json5::from_str::<ErrorRule>(json).is_err());
+    }
+
+    #[test]
+    fn rules_can_have_several_actions() {
+        let json = r#"{
+            "error_code": 114,
+            "action": ["quiet", "escalate", { "tag": "migration" }, { "highlight": { "foreground": "magenta", "attributes": ["bold"] } }]
+        }"#;
+        let rule: ErrorRule = json5::from_str(json).unwrap();
+        assert_eq!(rule.actions.len(), 4);
+        let rules = [
+            rule,
+            json5::from_str(r#"{ "error_code": 114, "action": { "tag": "legacy" } }"#).unwrap(),
+            json5::from_str(r#"{ "error_code": 114, "action": "suppress" }"#).unwrap(),
+        ];
+        let line = ImportLogLine {
+            code: "114".to_string(),
+            ..ImportLogLine::default()
+        };
+        let actions = apply_error_rules(&rules, &LineType::Error(line));
+        assert!(actions.quiet && actions.escalate && actions.suppress && !actions.ignore);
+        assert_eq!(actions.tag_text(), " [migration] [legacy]");
+        assert!(actions.highlight.is_some());
+        assert_eq!(
+            actions.names(),
+            ["quiet", "escalate", "suppress", "highlight", "tag"]
+        );
+
+        // no match, no actions
+        let line = ImportLogLine {
+            code: "3702".to_string(),
+            ..ImportLogLine::default()
+        };
+        assert!(!apply_error_rules(&rules, &LineType::Error(line)).is_some());
+    }
+
+    #[test]
+    fn invalid_actions_fail() {
+        for json in [
+            r#"{ "error_code": 1, "action": [] }"#,
+            r#"{ "error_code": 1, "action": ["quiet", "shout"] }"#,
+            r##"{ "error_code": 1, "action": { "highlight": { "foreground": "#12" } } }"##,
+            r#"{ "error_code": 1, "action": "tag" }"#,
+        ] {
+            assert!(json5::from_str::<ErrorRule>(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn rules_can_apply_to_other_line_types() {
+        let json = r#"[
+            { "line_type": "warning", "message_contains": "created and imported automatically", "action": "quiet" },
+            { "line_type": ["success", "other"], "message_starts_with": "0 ", "action": { "tag": "empty" } },
+            { "message_starts_with": "0 ", "action": "ignore" },
+            { "line_type": "header", "action": "suppress" },
+        ]"#;
+        let mut rules =
+            deserialize_error_rules(&mut json5::Deserializer::from_str(json).unwrap()).unwrap();
+        remove_no_match_rules(&mut rules);
+        // a bare line_type is enough to match
+        assert_eq!(rules.len(), 4);
+
+        let line = |message: &str| ImportLogLine {
+            message: message.to_string(),
+            ..ImportLogLine::default()
+        };
+        let warning = line("Missing file reference “x” created and imported automatically.");
+        assert!(apply_error_rules(&rules, &LineType::Warning(warning.clone())).quiet);
+        assert!(!apply_error_rules(&rules, &LineType::Error(warning)).is_some());
+
+        let success = apply_error_rules(&rules, &LineType::Success(line("0 records imported")));
+        assert_eq!(success.names(), ["tag"]);
+        let other = apply_error_rules(&rules, &LineType::Other("0 records imported".to_string()));
+        assert_eq!(other.names(), ["tag"]);
+        let error = apply_error_rules(&rules, &LineType::Error(line("0 records imported")));
+        assert_eq!(error.names(), ["ignore"]);
+        assert!(apply_error_rules(&rules, &LineType::Header(line("Message"))).suppress);
+
+        assert!(
+            json5::from_str::<ErrorRule>(r#"{ "line_type": "footer", "action": "quiet" }"#)
+                .is_err()
+        );
+        assert!(json5::from_str::<ErrorRule>(r#"{ "line_type": [], "action": "quiet" }"#).is_err());
+    }
+
+    #[test]
+    fn matches_name_their_rules() {
+        let json = r#"[
+            { "action": "quiet" },
+            { "name": "all 114s", "error_code": 114, "action": "quiet" },
+            { "message_contains": "missing", "action": { "tag": "missing" } },
+            { "error_code": 3702, "action": "ignore" },
+        ]"#;
+        let mut rules =
+            deserialize_error_rules(&mut json5::Deserializer::from_str(json).unwrap()).unwrap();
+        remove_no_match_rules(&mut rules);
+        // labels keep the position in the config
+        let labels: Vec<String> = rules.iter().map(ErrorRule::label).collect();
+        assert_eq!(
+            labels,
+            [
+                r#"error_rules[1] "all 114s""#,
+                "error_rules[2]",
+                "error_rules[3]"
+            ]
+        );
+
+        let line = ImportLogLine {
+            code: "114".to_string(),
+            message: "File reference missing.".to_string(),
+            ..ImportLogLine::default()
+        };
+        assert_eq!(
+            apply_error_rules(&rules, &LineType::Error(line)).rules,
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn thresholds_are_checked() {
+        let rule: ErrorRule = json5::from_str(
+            r#"{ "line_type": "error", "threshold": { "count": 10, "per_operation": true }, "action": "escalate" }"#,
+        )
+        .unwrap();
+        // a threshold is enough to match on
+        assert!(!rule.no_match_logic());
+        for json in [
+            r#"{ "error_code": 1, "threshold": { "count": 0 }, "action": "quiet" }"#,
+            r#"{ "error_code": 1, "threshold": { "count": 2, "within": -5 }, "action": "quiet" }"#,
+            r#"{ "error_code": 1, "threshold": { "count": 2, "per": "operation" }, "action": "quiet" }"#,
+        ] {
+            assert!(json5::from_str::<ErrorRule>(json).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn expired_rules_are_skipped() {
+        let mut rule: ErrorRule =
+            json5::from_str(r#"{ "error_code": 114, "expires": "8h", "action": "quiet" }"#)
+                .unwrap();
+        assert_eq!(rule.expires, Some(Expiry::After(Duration::hours(8))));
+        let line = LineType::Error(ImportLogLine {
+            code: "114".to_string(),
+            ..ImportLogLine::default()
+        });
+        let now = Local::now();
+        rule.resolve_expiry(now - Duration::hours(9));
+        assert!(rule.expired(now));
+        assert!(apply_error_rules(&[rule.clone()], &line).rules.is_empty());
+        rule.resolve_expiry(now);
+        assert!(rule.expired(now));
+
+        // a duration with its start is already a time
+        let rule: ErrorRule = json5::from_str(
+            r#"{ "error_code": 114, "expires": "8h from 2026-10-18 09:00", "action": "quiet" }"#,
+        )
+        .unwrap();
+        let at = parse_local_datetime("2026-10-18 17:00").unwrap();
+        assert_eq!(rule.expires_at(), Some(at));
+
+        let json = r#"[{ "error_code": 114, "expires": "2999-01-01", "action": "quiet" }]"#;
+        let mut rule = deserialize_error_rules(&mut json5::Deserializer::from_str(json).unwrap())
+            .unwrap()[0]
+            .clone();
+        assert!(!rule.expired(now));
+        assert_eq!(apply_error_rules(&[rule.clone()], &line).rules, [0]);
+        // only rules added by `mute` end early
+        assert!(!rule.end_mute("114", now));
+
+        let mut mute = ErrorRule::mute("114", now + Duration::minutes(30));
+        assert_eq!(mute.actions, [ErrorRuleAction::Quiet]);
+        assert_eq!(mute.index(), None);
+        assert_eq!(mute.label(), "mute 114");
+        assert!(!mute.end_mute("3702", now));
+        assert!(mute.end_mute("114", now));
+        assert!(mute.expired(now));
+        rule.expires = None;
+        assert!(!rule.expired(now));
+
+        for expires in ["soon", "8h from soon", "99999999d", "9999999999999999d"] {
+            assert!(json5::from_str::<ErrorRule>(&format!(
+                r#"{{ "error_code": 1, "expires": "{expires}", "action": "quiet" }}"#
+            ))
+            .is_err());
+        }
+    }
 }
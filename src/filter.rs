@@ -0,0 +1,325 @@
+use crate::config_file::Config;
+use crate::line_processor::ProcessedLine;
+use crate::query::Query;
+use crate::{Args, LineType};
+use regex::Regex;
+use std::str::FromStr;
+
+/// A column of an import log line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Column {
+    Timestamp,
+    Location,
+    Code,
+    Message,
+}
+
+impl FromStr for Column {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timestamp" => Ok(Column::Timestamp),
+            "location" => Ok(Column::Location),
+            "code" => Ok(Column::Code),
+            "message" => Ok(Column::Message),
+            _ => Err(format!(
+                "unknown column \"{s}\", expected one of timestamp, location, code, message"
+            )),
+        }
+    }
+}
+
+impl Column {
+    const ALL: [Column; 4] = [
+        Column::Timestamp,
+        Column::Location,
+        Column::Code,
+        Column::Message,
+    ];
+
+    /// the column's value, `None` for lines that aren't import log lines. Timestamps are matched as written in the log, not as displayed.
+    pub(crate) fn value<'a>(&self, line: &'a ProcessedLine) -> Option<&'a str> {
+        let (LineType::Success(l)
+        | LineType::Error(l)
+        | LineType::Warning(l)
+        | LineType::Header(l)) = &line.line
+        else {
+            return None;
+        };
+        Some(match self {
+            Column::Timestamp if line.raw_timestamp.is_empty() => &l.timestamp,
+            Column::Timestamp => &line.raw_timestamp,
+            Column::Location => &l.filename,
+            Column::Code => &l.code,
+            Column::Message => &l.message,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Text(String),
+    Regex(Regex),
+}
+
+/// An `--include`/`--exclude` pattern: `[column:]text` or `[column:]/regex/`. Without a column it matches anywhere in the line.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    column: Option<Column>,
+    matcher: Matcher,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // only split on a column name, exactly as documented, so patterns like `C:\Temp` or `Error: missing` still work.
+        // `message:Error: x` puts such text in a column, and `/code:1/` matches a column name anywhere
+        let (column, pattern) = match s.split_once(':') {
+            Some((column, pattern)) if Column::from_str(column).is_ok() => {
+                (Column::from_str(column).ok(), pattern)
+            }
+            _ => (None, s),
+        };
+        let matcher = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(regex) => Matcher::Regex(
+                Regex::new(regex).map_err(|e| format!("invalid regex in \"{s}\": {e}"))?,
+            ),
+            None => Matcher::Text(pattern.to_string()),
+        };
+        if matches!(&matcher, Matcher::Text(t) if t.is_empty()) {
+            return Err(format!("empty pattern \"{s}\""));
+        }
+        Ok(Pattern { column, matcher })
+    }
+}
+
+impl Pattern {
+    fn is_match_str(&self, s: &str) -> bool {
+        match &self.matcher {
+            Matcher::Text(text) => s.contains(text.as_str()),
+            Matcher::Regex(regex) => regex.is_match(s),
+        }
+    }
+
+    pub(crate) fn is_match(&self, line: &ProcessedLine) -> bool {
+        match (self.column, &line.line) {
+            (Some(column), _) => column.value(line).is_some_and(|v| self.is_match_str(v)),
+            (None, LineType::Other(text)) => self.is_match_str(text),
+            (None, _) => Column::ALL
+                .iter()
+                .filter_map(|c| c.value(line))
+                .any(|v| self.is_match_str(v)),
+        }
+    }
+}
+
+/// Decides which lines get shown. Headers are always shown.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineFilter {
+    pub(crate) errors_only: bool,
+    pub(crate) warnings_only: bool,
+    /// if not empty, lines must match at least one of these
+    pub(crate) include: Vec<Pattern>,
+    /// lines matching any of these are hidden
+    pub(crate) exclude: Vec<Pattern>,
+    /// the `--where` query
+    pub(crate) query: Option<Query>,
+}
+
+fn parse_patterns(patterns: &[String], path: &str) -> Result<Vec<Pattern>, String> {
+    patterns
+        .iter()
+        .enumerate()
+        .map(|(i, p)| Pattern::from_str(p).map_err(|e| format!("{path}[{i}]: {e}")))
+        .collect()
+}
+
+impl LineFilter {
+    /// Combine the type filters and patterns from the command line with the config's patterns and any selected filter sets
+    pub(crate) fn new(args: &Args, config: &Config) -> Result<Self, String> {
+        let mut include = args.include.clone();
+        let mut exclude = args.exclude.clone();
+        include.extend(parse_patterns(&config.include, "include")?);
+        exclude.extend(parse_patterns(&config.exclude, "exclude")?);
+        for name in &args.filter_set {
+            let Some(set) = config.filter_sets.get(name) else {
+                let mut names = config.filter_sets.keys().cloned().collect::<Vec<_>>();
+                names.sort();
+                return Err(format!(
+                    "unknown filter set \"{name}\". Filter sets in the config: {}",
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ));
+            };
+            include.extend(parse_patterns(
+                &set.include,
+                &format!("filter_sets.{name}.include"),
+            )?);
+            exclude.extend(parse_patterns(
+                &set.exclude,
+                &format!("filter_sets.{name}.exclude"),
+            )?);
+        }
+        let query = match (&args.query, config.query.is_empty()) {
+            (Some(query), _) => Some(query.clone()),
+            (None, false) => {
+                Some(Query::from_str(&config.query).map_err(|e| format!("where: {e}"))?)
+            }
+            (None, true) => None,
+        };
+        Ok(LineFilter {
+            errors_only: args.errors_only,
+            warnings_only: args.warnings_only,
+            include,
+            exclude,
+            query,
+        })
+    }
+
+    /// true when only some line types are shown
+    pub(crate) fn is_type_active(&self) -> bool {
+        self.errors_only || self.warnings_only
+    }
+
+    /// true when some lines may be hidden
+    pub(crate) fn is_active(&self) -> bool {
+        self.is_type_active()
+            || !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || self.query.is_some()
+    }
+
+    /// only show the given line types, keeping the patterns
+    pub(crate) fn set_types(&mut self, errors_only: bool, warnings_only: bool) {
+        self.errors_only = errors_only;
+        self.warnings_only = warnings_only;
+    }
+
+    pub(crate) fn shows(&self, line: &ProcessedLine) -> bool {
+        let l = &line.line;
+        // suppressed by an error rule
+        if line.actions.suppress {
+            return false;
+        }
+        if l.is_header() {
+            return true;
+        }
+        let type_shown = (self.errors_only && l.is_error())
+            || (self.warnings_only && l.is_warning())
+            || !self.is_type_active();
+        type_shown
+            && (self.include.is_empty() || self.include.iter().any(|p| p.is_match(line)))
+            && !self.exclude.iter().any(|p| p.is_match(line))
+            && self.query.as_ref().is_none_or(|q| q.is_match(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_processor::LineProcessor;
+
+    const ERROR: &str =
+        "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t114\tFile reference “DA9FD4AF” missing.";
+    const SUCCESS: &str =
+        "2023-05-03 08:58:37.215 +0200\tMissing EDS\t0\tscript steps imported : 1";
+
+    fn lines() -> (ProcessedLine, ProcessedLine) {
+        let mut processor = LineProcessor::new(&Args::default());
+        (processor.process(ERROR), processor.process(SUCCESS))
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> LineFilter {
+        LineFilter {
+            include: include.iter().map(|p| p.parse().unwrap()).collect(),
+            exclude: exclude.iter().map(|p| p.parse().unwrap()).collect(),
+            ..LineFilter::default()
+        }
+    }
+
+    #[test]
+    fn parse_patterns() {
+        let pattern = Pattern::from_str("location:/Open File$/").unwrap();
+        assert_eq!(pattern.column, Some(Column::Location));
+        assert!(matches!(pattern.matcher, Matcher::Regex(_)));
+
+        // not a column, so the whole thing is the pattern
+        let pattern = Pattern::from_str(r"C:\Temp").unwrap();
+        assert_eq!(pattern.column, None);
+
+        // column names are lowercase, with no aliases
+        for s in ["Error: foo", "Message: x", "time:out", "code :1"] {
+            let pattern = Pattern::from_str(s).unwrap();
+            assert_eq!(pattern.column, None, "{s}");
+            assert!(
+                matches!(&pattern.matcher, Matcher::Text(t) if t == s),
+                "{s}"
+            );
+        }
+        let pattern = Pattern::from_str("message:Error: x").unwrap();
+        assert_eq!(pattern.column, Some(Column::Message));
+        assert!(matches!(&pattern.matcher, Matcher::Text(t) if t == "Error: x"));
+
+        assert!(Pattern::from_str("message:/(unclosed/").is_err());
+        assert!(Pattern::from_str("code:").is_err());
+    }
+
+    #[test]
+    fn include_and_exclude() {
+        let (error, success) = lines();
+        let f = filter(&["code:114"], &[]);
+        assert!(f.shows(&error) && !f.shows(&success));
+
+        let f = filter(&["Missing EDS"], &["message:/imported : \\d+/"]);
+        assert!(f.shows(&error) && !f.shows(&success));
+
+        // columns are matched separately
+        let f = filter(&["location:script steps"], &[]);
+        assert!(!f.shows(&success));
+
+        let f = filter(&["timestamp:08:58:37.214"], &[]);
+        assert!(f.shows(&error) && !f.shows(&success));
+
+        // not a column prefix, so it's matched against the whole line
+        let f = filter(&["Error: foo"], &[]);
+        let mut processor = LineProcessor::new(&Args::default());
+        let line = processor
+            .process("2023-05-03 08:58:37.214 +0200\tMissing EDS\t114\tError: foo in the script");
+        assert!(f.shows(&line) && !f.shows(&error));
+    }
+
+    #[test]
+    fn patterns_combine_with_type_filters() {
+        let (error, success) = lines();
+        let mut f = filter(&["Missing"], &[]);
+        f.set_types(false, true);
+        assert!(!f.shows(&error) && !f.shows(&success));
+        f.set_types(true, false);
+        assert!(f.shows(&error));
+    }
+
+    #[test]
+    fn filter_sets_from_config() {
+        let config: Config = json5::from_str(
+            r#"{ filter_sets: { eds: { include: ["location:EDS"], exclude: ["code:0"] } } }"#,
+        )
+        .unwrap();
+        let args = Args {
+            filter_set: vec!["eds".to_string()],
+            ..Args::default()
+        };
+        let f = LineFilter::new(&args, &config).unwrap();
+        let (error, success) = lines();
+        assert!(f.shows(&error) && !f.shows(&success));
+
+        let args = Args {
+            filter_set: vec!["nope".to_string()],
+            ..Args::default()
+        };
+        let err = LineFilter::new(&args, &config).unwrap_err();
+        assert!(err.contains("eds"));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::color_support::ColorSupport;
+use crate::config_file::ConfigHighlight;
+use crate::style::{Attribute, Style};
+use regex::{Regex, RegexBuilder};
+
+// the order matters: earlier rules win where matches overlap
+const BUILTIN_HIGHLIGHTS: &[(&str, &str)] = &[
+    (
+        "guid",
+        r"\b[0-9A-Fa-f]{8}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{4}-[0-9A-Fa-f]{12}\b",
+    ),
+    // “English”, „German“, 「Japanese」, «French» and "plain" quotes
+    (
+        "quoted",
+        r#"“[^”\t]*”|„[^“”\t]*[“”]|「[^」\t]*」|«[^»\t]*»|"[^"\t]*""#,
+    ),
+    (
+        "path",
+        r#"(?:file(?:mac|win)?:)?(?:/[^\s/“”"」]+){2,}/?|\b[A-Za-z]:\\[^\s“”"」]+"#,
+    ),
+    ("number", r"\b\d+(?:[.,]\d+)?\b"),
+];
+
+fn builtin_style(name: &str) -> Style {
+    // attributes only, so the built-ins read well with any theme
+    let attribute = match name {
+        "guid" => Attribute::Dimmed,
+        "path" => Attribute::Underline,
+        _ => Attribute::Bold,
+    };
+    Style {
+        attributes: vec![attribute],
+        ..Style::default()
+    }
+}
+
+/// a case-insensitive regex matching `query` literally, or `None` if the query is empty
+pub(crate) fn search_regex(query: &str) -> Option<Regex> {
+    if query.is_empty() {
+        return None;
+    }
+    RegexBuilder::new(&regex::escape(query))
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+pub(crate) fn builtin_highlight_names() -> Vec<&'static str> {
+    BUILTIN_HIGHLIGHTS.iter().map(|(name, _)| *name).collect()
+}
+
+struct HighlightRule {
+    regex: Regex,
+    style: Style,
+}
+
+/// Highlights tokens inside messages, like quoted object names, GUIDs, paths and numbers
+#[derive(Default)]
+pub(crate) struct Highlighter {
+    rules: Vec<HighlightRule>,
+    /// the runtime search term, drawn reversed on top of everything else
+    search: Option<HighlightRule>,
+}
+
+impl Highlighter {
+    /// Custom rules come first so they win over the built-ins. A config entry with `builtin` set restyles or disables that built-in.
+    pub(crate) fn from_config(highlights: &[ConfigHighlight]) -> Result<Self, String> {
+        let mut rules = vec![];
+        for (i, highlight) in highlights.iter().enumerate() {
+            let path = format!("highlights[{i}]");
+            match (highlight.builtin.is_empty(), highlight.pattern.is_empty()) {
+                (true, false) => {}
+                (false, true) => {
+                    if !builtin_highlight_names().contains(&highlight.builtin.as_str()) {
+                        return Err(format!(
+                            "{path}.builtin: unknown built-in highlight \"{}\", expected one of {}",
+                            highlight.builtin,
+                            builtin_highlight_names().join(", ")
+                        ));
+                    }
+                    continue;
+                }
+                _ => return Err(format!("{path}: set either `pattern` or `builtin`")),
+            }
+            if !highlight.enabled.unwrap_or(true) {
+                continue;
+            }
+            let regex = Regex::new(&highlight.pattern)
+                .map_err(|e| format!("{path}.pattern: invalid regex: {e}"))?;
+            let style = Style::default().merge(&highlight.color, &path)?;
+            rules.push(HighlightRule { regex, style });
+        }
+
+        for (name, pattern) in BUILTIN_HIGHLIGHTS {
+            let mut style = builtin_style(name);
+            let mut enabled = true;
+            for (i, highlight) in highlights.iter().enumerate() {
+                if highlight.builtin == *name {
+                    enabled = highlight.enabled.unwrap_or(true);
+                    style = style.merge(&highlight.color, &format!("highlights[{i}]"))?;
+                }
+            }
+            if enabled {
+                let regex = Regex::new(pattern).expect("built-in highlight regex is invalid");
+                rules.push(HighlightRule { regex, style });
+            }
+        }
+
+        Ok(Highlighter {
+            rules,
+            search: None,
+        })
+    }
+
+    pub(crate) fn downgrade(mut self, support: ColorSupport) -> Self {
+        for rule in &mut self.rules {
+            rule.style = rule.style.downgrade(support);
+        }
+        self
+    }
+
+    pub(crate) fn set_search(&mut self, regex: Option<Regex>) {
+        self.search = regex.map(|regex| HighlightRule {
+            regex,
+            style: Style {
+                attributes: vec![Attribute::Reversed],
+                ..Style::default()
+            },
+        });
+    }
+
+    /// Paint `text` with `base`, highlighting only the search term. Used for columns that don't get the regular highlights.
+    pub(crate) fn paint_search(&self, text: &str, base: &Style) -> String {
+        segments(self.search.iter(), text, base)
+            .into_iter()
+            .map(|(segment, style)| style.paint(segment))
+            .collect()
+    }
+
+    /// Paint `text` with `base`, layering the style of every rule that matches on top. Where matches overlap, the earlier rule's colors win.
+    pub(crate) fn paint(&self, text: &str, base: &Style) -> String {
+        self.segments(text, base)
+            .into_iter()
+            .map(|(segment, style)| style.paint(segment))
+            .collect()
+    }
+
+    /// Split `text` into runs that share a style. Used by `paint`, and by the TUI which draws styles itself.
+    pub(crate) fn segments<'a>(&self, text: &'a str, base: &Style) -> Vec<(&'a str, Style)> {
+        segments(self.search.iter().chain(&self.rules), text, base)
+    }
+}
+
+/// `rules` are in priority order, highest first
+fn segments<'a, 'r>(
+    rules: impl Iterator<Item = &'r HighlightRule>,
+    text: &'a str,
+    base: &Style,
+) -> Vec<(&'a str, Style)> {
+    let rules = rules.collect::<Vec<_>>();
+    // (start, end, rule index)
+    let mut matches = vec![];
+    for (i, rule) in rules.iter().enumerate() {
+        for m in rule.regex.find_iter(text) {
+            if !m.is_empty() {
+                matches.push((m.start(), m.end(), i));
+            }
+        }
+    }
+    if matches.is_empty() {
+        return vec![(text, base.clone())];
+    }
+
+    let mut boundaries = vec![0, text.len()];
+    for (start, end, _) in &matches {
+        boundaries.push(*start);
+        boundaries.push(*end);
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut res = vec![];
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+        // apply the lowest priority rule first so higher priority rules are layered on top
+        let mut style = base.clone();
+        let mut covering = matches
+            .iter()
+            .filter(|(s, e, _)| *s <= start && end <= *e)
+            .map(|(_, _, i)| *i)
+            .collect::<Vec<_>>();
+        covering.sort_unstable_by(|a, b| b.cmp(a));
+        covering.dedup();
+        for i in covering {
+            style = style.layer(&rules[i].style);
+        }
+        res.push((&text[start..end], style));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_file::ConfigColor;
+
+    fn custom(pattern: &str, color: ConfigColor) -> ConfigHighlight {
+        ConfigHighlight {
+            pattern: pattern.to_string(),
+            color,
+            ..ConfigHighlight::default()
+        }
+    }
+
+    fn strip_ansi(s: &str) -> String {
+        Regex::new(r"\x1b\[[0-9;]*m")
+            .unwrap()
+            .replace_all(s, "")
+            .to_string()
+    }
+
+    fn matched_tokens(highlighter: &Highlighter, text: &str) -> Vec<String> {
+        let mut res = vec![];
+        for rule in &highlighter.rules {
+            for m in rule.regex.find_iter(text) {
+                res.push(m.as_str().to_string());
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn builtins_match_expected_tokens() {
+        let highlighter = Highlighter::from_config(&[]).unwrap();
+        let tokens = matched_tokens(
+            &highlighter,
+            "File reference “DA9FD4AF-B5E2-41CE-807F-ECA0A9CF44C1” missing.",
+        );
+        assert_eq!(
+            tokens,
+            [
+                "DA9FD4AF-B5E2-41CE-807F-ECA0A9CF44C1",
+                "“DA9FD4AF-B5E2-41CE-807F-ECA0A9CF44C1”"
+            ]
+        );
+
+        let tokens = matched_tokens(&highlighter, "script steps imported : 12");
+        assert_eq!(tokens, ["12"]);
+
+        let tokens = matched_tokens(&highlighter, "テーブル「MyTable」は既に存在します。");
+        assert_eq!(tokens, ["「MyTable」"]);
+
+        let tokens = matched_tokens(&highlighter, "Feld „MyField“ importiert");
+        assert_eq!(tokens, ["„MyField“"]);
+
+        let tokens = matched_tokens(
+            &highlighter,
+            r"opened filemac:/HD/Users/file.fmp12 and C:\Temp",
+        );
+        assert_eq!(tokens, ["filemac:/HD/Users/file.fmp12", r"C:\Temp"]);
+    }
+
+    #[test]
+    fn builtins_can_be_disabled() {
+        let highlights: Vec<ConfigHighlight> =
+            json5::from_str(r#"[{ builtin: "number", enabled: false }]"#).unwrap();
+        let highlighter = Highlighter::from_config(&highlights).unwrap();
+        assert!(matched_tokens(&highlighter, "fields imported : 1").is_empty());
+    }
+
+    #[test]
+    fn custom_rules_come_first() {
+        let highlighter = Highlighter::from_config(&[custom(
+            "Open File",
+            ConfigColor {
+                foreground: "red".to_string(),
+                ..ConfigColor::default()
+            },
+        )])
+        .unwrap();
+        assert_eq!(highlighter.rules.len(), 1 + BUILTIN_HIGHLIGHTS.len());
+        assert!(highlighter.rules[0].regex.is_match("Open File"));
+    }
+
+    #[test]
+    fn invalid_config_is_err() {
+        let highlights: Vec<ConfigHighlight> =
+            json5::from_str(r#"[{ pattern: "a" }, { pattern: "(unclosed" }]"#).unwrap();
+        let err = Highlighter::from_config(&highlights).err().unwrap();
+        assert!(err.starts_with("highlights[1].pattern: invalid regex"));
+
+        let highlights: Vec<ConfigHighlight> = json5::from_str(r#"[{ builtin: "nope" }]"#).unwrap();
+        assert!(Highlighter::from_config(&highlights).is_err());
+
+        let highlights: Vec<ConfigHighlight> =
+            json5::from_str(r#"[{ builtin: "guid", pattern: "x" }]"#).unwrap();
+        assert!(Highlighter::from_config(&highlights).is_err());
+
+        let highlights: Vec<ConfigHighlight> =
+            json5::from_str(r#"[{ pattern: "x", foreground: "not a color" }]"#).unwrap();
+        assert!(Highlighter::from_config(&highlights).is_err());
+    }
+
+    #[test]
+    fn search_wins_over_other_highlights() {
+        let mut highlighter = Highlighter::from_config(&[]).unwrap();
+        highlighter.set_search(search_regex("FIELD 2"));
+        let segments = highlighter.segments("imported “MyField 2”", &Style::default());
+        let search = segments
+            .iter()
+            .filter(|(_, style)| style.attributes.contains(&Attribute::Reversed))
+            .map(|(text, _)| *text)
+            .collect::<String>();
+        assert_eq!(search, "Field 2");
+
+        assert!(strip_ansi(&highlighter.paint_search("no match", &Style::default())) == "no match");
+    }
+
+    #[test]
+    fn paint_keeps_text_intact() {
+        let highlighter = Highlighter::from_config(&[]).unwrap();
+        let text = "File reference “DA9FD4AF-B5E2-41CE-807F-ECA0A9CF44C1” missing. 3 of 4";
+        let painted = highlighter.paint(text, &Style::default());
+        assert_eq!(strip_ansi(&painted), text);
+    }
+}
@@ -1,30 +1,57 @@
 mod beeper;
+mod color_support;
 mod color_type;
+mod commands;
 mod config_file;
+mod dedup;
 mod error_rule;
+mod filter;
+mod highlight;
+mod line_processor;
+mod log_file;
 mod notifications;
+mod printer;
+mod query;
+mod rule_lint;
+mod rule_tester;
 mod rules;
+mod session;
+mod style;
+mod theme;
+mod threshold;
+mod timestamp;
+mod tui;
 mod utils;
 
 use beeper::beep;
-use color_type::ColorType;
-use config_file::{get_config, get_default_config_path, update_args_from_config, ConfigColor};
-use error_rule::{apply_error_rules, ErrorRule, ErrorRuleAction};
+use color_support::{ColorMode, ColorSupport};
+use config_file::{get_config, get_default_config_path, update_args_from_config};
+use dedup::Deduper;
+use error_rule::{deserialize_error_rules, remove_no_match_rules, ErrorRule};
+use filter::{LineFilter, Pattern};
+use highlight::Highlighter;
+use line_processor::LineProcessor;
+use log_file::LogFile;
 use notifications::NotificationType;
-use rules::{contains_warning_text, is_header, is_operation_start};
-use utils::{clear_terminal, is_timestamp, replace_trailing_cr_with_crlf};
-
-use clap::{Command, CommandFactory, Parser, ValueHint};
+use printer::{Context, Printer};
+use query::Query;
+use rules::{contains_warning_text, is_header};
+use session::{Session, DEFAULT_HISTORY_SIZE};
+use style::LineStyles;
+use theme::load_theme;
+use timestamp::TimestampFormat;
+use utils::{is_timestamp, replace_trailing_cr_with_crlf};
+
+use chrono::Local;
+use clap::{Command, CommandFactory, Parser, Subcommand, ValueHint};
 use clap_complete::{generate, Generator, Shell};
-use colored::{ColoredString, Colorize};
-use notify::RecursiveMode;
-use notify_debouncer_mini::new_debouncer;
+use colored::Colorize;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, Read, Seek, SeekFrom};
+use std::io::BufRead;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::mpsc;
-use std::time::Duration;
+use std::sync::{mpsc, Arc, Mutex};
 use std::{env, io};
 
 type CustomResult<T = ()> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -66,6 +93,20 @@ pub struct Args {
     #[arg(long, help = "Don't print color")]
     no_color: bool,
 
+    #[arg(
+        long,
+        help = "Color support of the terminal. Colors the terminal can't display are mapped to the nearest supported color. Defaults to auto",
+        value_name = "MODE",
+        value_enum
+    )]
+    color_mode: Option<ColorMode>,
+
+    #[arg(
+        long,
+        help = "Don't highlight quoted names, GUIDs, paths and numbers inside messages"
+    )]
+    no_highlights: bool,
+
     #[arg(
         long,
         short,
@@ -80,9 +121,117 @@ pub struct Args {
     )]
     warnings_only: bool,
 
+    #[arg(
+        long,
+        help = "Only print lines matching PATTERN. Repeat to allow several. PATTERN is text or /regex/, optionally limited to one column with a timestamp:, location:, code: or message: prefix",
+        value_name = "PATTERN"
+    )]
+    include: Vec<Pattern>,
+
+    #[arg(
+        long,
+        help = "Don't print lines matching PATTERN. Repeatable, same format as --include",
+        value_name = "PATTERN"
+    )]
+    exclude: Vec<Pattern>,
+
+    #[arg(
+        long = "where",
+        help = "Only print lines matching QUERY, e.g. 'code in (114, 3702) and location ~ \"Open File\" and not message contains temp'. Fields: timestamp, location, code, message, type, operation, action",
+        value_name = "QUERY"
+    )]
+    query: Option<Query>,
+
+    #[arg(
+        long,
+        help = "Apply a named set of include/exclude patterns from the config's filter_sets. Repeatable",
+        value_name = "NAME"
+    )]
+    filter_set: Vec<String>,
+
+    #[arg(
+        long,
+        short = 'A',
+        help = "Print NUM hidden lines after each shown error or warning, dimmed. Only useful with filters like --errors-only",
+        value_name = "NUM"
+    )]
+    after_context: Option<usize>,
+
+    #[arg(
+        long,
+        short = 'B',
+        help = "Print NUM hidden lines before each shown error or warning, dimmed. Only useful with filters like --errors-only",
+        value_name = "NUM"
+    )]
+    before_context: Option<usize>,
+
+    #[arg(long, short = 'C', help = "Same as -A NUM -B NUM", value_name = "NUM")]
+    context: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Print the start line of the import operation before its first shown error or warning, dimmed"
+    )]
+    operation_context: bool,
+
+    #[arg(
+        long,
+        help = "Print repeated errors and warnings (same code and message, ignoring GUIDs and numbers) once per import operation, with a ×N count of the repeats"
+    )]
+    dedup: bool,
+
+    #[arg(
+        long,
+        help = "With --dedup, count repeats within SECONDS of the previous one instead of within the import operation. Implies --dedup",
+        value_name = "SECONDS",
+        value_parser(parse_dedup_window)
+    )]
+    dedup_window: Option<f64>,
+
+    #[arg(
+        long,
+        help = "How many of the latest lines to keep for search and resume. Defaults to 10000",
+        value_name = "LINES"
+    )]
+    history_size: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Hold back each import operation until it completes. Print it in full if it had an error or warning, otherwise print a single \"✔ operation OK\" line"
+    )]
+    collapse_ok: bool,
+
+    #[arg(
+        long,
+        help = "After each line matched by error rules, print which rules matched. Print how many lines each rule matched on exit"
+    )]
+    explain_rules: bool,
+
     #[arg(long, short, help = "Print a separator between each import operation")]
     separator: bool,
 
+    #[arg(
+        long,
+        help = "Full-screen interactive view with scrollback, search, filters and collapsible import operations"
+    )]
+    tui: bool,
+
+    #[arg(
+        long,
+        help = "Color theme. Either a built-in theme (dark, light, high-contrast, solarized, monochrome) or a path to a theme file. The config's colors are layered on top",
+        value_name = "THEME",
+        value_hint = ValueHint::FilePath
+    )]
+    theme: Option<String>,
+
+    #[arg(
+        long,
+        help = "How to display timestamps. Defaults to full",
+        value_name = "FORMAT",
+        value_enum
+    )]
+    timestamp_format: Option<TimestampFormat>,
+
     #[arg(long, help = "Show desktop notifications on errors and warnings")]
     notifications: bool,
 
@@ -108,10 +257,19 @@ pub struct Args {
     )]
     beep_path: String,
 
+    #[arg(
+        long,
+        help = "Beep sound file for errors matched by an escalate rule. Defaults to /System/Library/Sounds/Sosumi.aiff",
+        value_name = "PATH",
+        default_value = "/System/Library/Sounds/Sosumi.aiff"
+    )]
+    escalate_beep_path: String,
+
     // IDK why this special Vec syntax works, but it does. See https://github.com/clap-rs/clap/issues/4626
     #[arg(
         long,
-        help = "JSON array of error rules. Controls the behavior when an error line matches one or more rules.",
+        visible_alias = "line-rules",
+        help = "JSON array of error rules. Controls the behavior when a line matches one or more rules. Rules apply to error lines unless they set `line_type`.",
         value_name = "ERROR_RULES",
         value_parser(parse_error_rule_array),
         default_value = "[]"
@@ -126,8 +284,6 @@ pub struct Args {
     )]
     quiet_errors: Vec<String>,
 
-    // how should filter be passed in? what if we want multiple filters?
-    //   - maybe some basic filters and a regex option?
     #[arg(
         long,
         help = "Create log file if missing. This happens automatically when using the --docs-dir option."
@@ -147,13 +303,86 @@ pub struct Args {
 
     #[arg(long, help = "generate completion script")]
     completion: Option<Shell>,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Check the error rules in the config
+    #[command(subcommand)]
+    Rules(RulesCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesCommand {
+    /// Report rules that are empty, duplicated, shadowed by an earlier rule, contradictory, or that match nothing in a sample log. Exits with 1 if there are any
+    Lint {
+        #[arg(
+            long = "config",
+            short = 'c',
+            help = "Path to the config file with the error rules. Defaults to the default config path",
+            value_name = "PATH"
+        )]
+        config_path: Option<String>,
+
+        #[arg(
+            long,
+            help = "Also report rules that match no line of this log",
+            value_name = "LOG",
+            value_hint = ValueHint::FilePath
+        )]
+        sample: Option<PathBuf>,
+    },
+    /// Print what the rules do to each line of a log line or file: the parsed fields, the matching rules and their actions
+    Test {
+        #[arg(
+            long = "config",
+            short = 'c',
+            help = "Path to the config file with the error rules. Defaults to the default config path",
+            value_name = "PATH"
+        )]
+        config_path: Option<String>,
+
+        #[arg(
+            help = "A log line to test, with tabs between the columns",
+            required_unless_present = "log",
+            conflicts_with = "log"
+        )]
+        line: Option<String>,
+
+        #[arg(
+            long,
+            help = "Test every line of this log instead",
+            value_name = "LOG",
+            value_hint = ValueHint::FilePath
+        )]
+        log: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "JSON file of expected results, e.g. [{ \"line\": 3, \"actions\": [\"quiet\"], \"rules\": [\"my rule\"] }]. Exits with 1 if any don't match",
+            value_name = "FILE",
+            value_hint = ValueHint::FilePath
+        )]
+        expect: Option<PathBuf>,
+    },
 }
 
 fn parse_error_rule_array(val: &str) -> Result<Vec<ErrorRule>, String> {
-    let rules = serde_json::from_str(val).map_err(|e| e.to_string())?;
+    let mut deserializer = serde_json::Deserializer::from_str(val);
+    let rules = deserialize_error_rules(&mut deserializer).map_err(|e| e.to_string())?;
     Ok(rules)
 }
 
+fn parse_dedup_window(val: &str) -> Result<f64, String> {
+    let secs: f64 = val
+        .parse()
+        .map_err(|_| format!("expected a number of seconds, got \"{val}\""))?;
+    dedup::check_window(secs)
+}
+
 #[derive(Clone, Default)]
 struct ImportLogLine {
     timestamp: String,
@@ -166,15 +395,17 @@ impl ImportLogLine {
         self.code != "0"
     }
 }
-impl ToString for ImportLogLine {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for ImportLogLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
             "{}\t{}\t{}\t{}",
             self.timestamp, self.filename, self.code, self.message
         )
     }
 }
 
+#[derive(Clone)]
 enum LineType {
     Success(ImportLogLine),
     Error(ImportLogLine),
@@ -192,6 +423,16 @@ impl LineType {
     fn is_warning(&self) -> bool {
         matches!(self, LineType::Warning(_))
     }
+    /// as used by rules' line_type and the query language
+    fn type_name(&self) -> &'static str {
+        match self {
+            LineType::Error(_) => "error",
+            LineType::Warning(_) => "warning",
+            LineType::Success(_) => "success",
+            LineType::Header(_) => "header",
+            LineType::Other(_) => "other",
+        }
+    }
     #[cfg(test)]
     fn is_success(&self) -> bool {
         matches!(self, LineType::Success(_))
@@ -201,14 +442,14 @@ impl LineType {
         matches!(self, LineType::Other(_))
     }
 }
-impl ToString for LineType {
-    fn to_string(&self) -> String {
+impl fmt::Display for LineType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            LineType::Error(line) => line.to_string(),
-            LineType::Header(line) => line.to_string(),
-            LineType::Other(line) => line.to_string(),
-            LineType::Success(line) => line.to_string(),
-            LineType::Warning(line) => line.to_string(),
+            LineType::Error(line) => line.fmt(f),
+            LineType::Header(line) => line.fmt(f),
+            LineType::Other(line) => line.fmt(f),
+            LineType::Success(line) => line.fmt(f),
+            LineType::Warning(line) => line.fmt(f),
         }
     }
 }
@@ -315,72 +556,46 @@ fn get_path_type(args: &Args) -> CustomResult<PathType> {
     }
 }
 
-fn get_default_colorizer(
-    config_color: ConfigColor,
-    default_foreground: String,
-) -> impl Fn(&str) -> ColoredString {
-    move |line: &str| {
-        let foreground = if config_color.foreground.is_empty() {
-            default_foreground.as_str()
-        } else {
-            config_color.foreground.as_str()
-        };
-        let background = if config_color.background.is_empty() {
-            ""
-        } else {
-            config_color.background.as_str()
-        };
-
-        let mut res = match ColorType::from_str(foreground).unwrap_or_default() {
-            ColorType::Rgb(r, g, b) => line.truecolor(r, g, b),
-            ColorType::Ansi(ansi) => line.color(ansi),
-        };
-        if !background.is_empty() {
-            res = match ColorType::from_str(background) {
-                Ok(color_type) => match color_type {
-                    ColorType::Rgb(r, g, b) => res.on_truecolor(r, g, b),
-                    ColorType::Ansi(ansi) => res.on_color(ansi),
-                },
-                Err(_) => res,
-            };
-        }
-        res
-    }
-}
-
-fn colorize_columns(
-    line: &ImportLogLine,
-    timestamp_colorizer: &impl Fn(&str) -> ColoredString,
-    filename_colorizer: &impl Fn(&str) -> ColoredString,
-    error_colorizer: &impl Fn(&str) -> ColoredString,
-    message_colorizer: &impl Fn(&str) -> ColoredString,
-) -> [ColoredString; 4] {
-    let ts = timestamp_colorizer(&line.timestamp);
-    let filename = filename_colorizer(&line.filename);
-    let error = error_colorizer(&line.code);
-    let msg = message_colorizer(&line.message);
-    [ts, filename, error, msg]
-}
-
 fn generate_completion_script<G: Generator>(gen: G, cmd: &mut Command) {
     generate(gen, cmd, cmd.get_name().to_string(), &mut io::stdout());
 }
 
-fn listen_for_keyboard_input() -> std::thread::JoinHandle<()> {
+fn listen_for_keyboard_input(session: Arc<Mutex<Session>>) -> std::thread::JoinHandle<()> {
     // do this in a separate thread so that we can listen for user input while the main thread is blocked on the debouncer
-    std::thread::spawn(|| {
+    std::thread::spawn(move || {
         let stdin = io::stdin();
         for line in BufRead::lines(stdin.lock()) {
             let line = line.unwrap();
-            if line == "q" || line == "quit" || line == "exit" {
-                std::process::exit(0);
-            } else if line == "clear" {
-                clear_terminal();
+            match commands::Command::from_str(&line) {
+                Ok(commands::Command::Quit) => {
+                    session.lock().unwrap().finish();
+                    std::process::exit(0)
+                }
+                Ok(command) => session.lock().unwrap().run_command(command),
+                Err(e) => eprintln!("{}", e),
             }
         }
     })
 }
 
+/// On Ctrl-C, print what `quit` prints (like the error rule hits) before exiting
+#[cfg(unix)]
+fn listen_for_interrupt(session: Arc<Mutex<Session>>) {
+    use signal_hook::consts::SIGINT;
+    use signal_hook::iterator::Signals;
+    let Ok(mut signals) = Signals::new([SIGINT]) else {
+        return;
+    };
+    std::thread::spawn(move || {
+        if signals.forever().next().is_some() {
+            // start after the ^C the terminal echoed
+            println!();
+            session.lock().unwrap().finish();
+            std::process::exit(130);
+        }
+    });
+}
+
 pub fn run() -> CustomResult {
     #[cfg(target_os = "windows")]
     colored::control::set_virtual_terminal(true).unwrap();
@@ -400,7 +615,29 @@ pub fn run() -> CustomResult {
         return Ok(());
     }
 
-    let config = get_config(args.config_path.as_deref())?;
+    match &args.command {
+        Some(CliCommand::Rules(RulesCommand::Lint {
+            config_path,
+            sample,
+        })) => return rule_lint::run(config_path.as_deref(), sample.as_deref()),
+        Some(CliCommand::Rules(RulesCommand::Test {
+            config_path,
+            line,
+            log,
+            expect,
+        })) => {
+            return rule_tester::run(
+                config_path.as_deref(),
+                line.as_deref(),
+                log.as_deref(),
+                expect.as_deref(),
+            )
+        }
+        None => {}
+    }
+
+    let mut config = get_config(args.config_path.as_deref())?;
+    let ignored_rules = remove_no_match_rules(&mut config.error_rules);
     update_args_from_config(&mut args, &config);
 
     let path_type = get_path_type(&args)?;
@@ -413,29 +650,84 @@ pub fn run() -> CustomResult {
     )?;
     path_type.print_message(args.no_color);
 
-    // get colorizer for each field:
-    let timestamp_colorizer = get_default_colorizer(config.colors.timestamp, "cyan".to_string());
-    let filename_colorizer = get_default_colorizer(config.colors.filename, "green".to_string());
-    let error_colorizer = get_default_colorizer(config.colors.error, "bright magenta".to_string());
-    let message_colorizer = get_default_colorizer(config.colors.message, "bright blue".to_string());
+    let mut theme = load_theme(args.theme.as_deref().unwrap_or("dark"))?;
+    theme.layer(&config.colors, &config.line_styles);
+    let color_support = if args.no_color {
+        ColorSupport::None
+    } else {
+        args.color_mode.unwrap_or_default().resolve()
+    };
+    colored::control::set_override(color_support != ColorSupport::None);
+    let styles = LineStyles::from_config(&theme.colors, &theme.line_styles)
+        .map_err(|e| format!("invalid color config: {}", e))?
+        .downgrade(color_support);
+    for rule in &mut args.error_rules {
+        rule.downgrade(color_support);
+    }
+    let highlighter = if args.no_highlights {
+        Highlighter::default()
+    } else {
+        Highlighter::from_config(&config.highlights)
+            .map_err(|e| format!("invalid highlight config: {}", e))?
+            .downgrade(color_support)
+    };
 
     // Init notifications. Create a channel whether we send notifications or not because the handle_line closure needs one, even if the messages go nowhere.
     let (notif_tx, notif_rx) = mpsc::channel();
+    let (beep_path, escalate_beep_path, beep_volume) = (
+        args.beep_path.clone(),
+        args.escalate_beep_path.clone(),
+        args.beep_volume,
+    );
+    let beep_for = move |escalated: bool| {
+        let path = if escalated {
+            &escalate_beep_path
+        } else {
+            &beep_path
+        };
+        beep(path, beep_volume);
+    };
     if args.notifications && args.beep {
-        notifications::listen(notif_rx, move |notif| {
-            beep(&args.beep_path, args.beep_volume);
+        notifications::listen(notif_rx, move |notif, escalated| {
+            beep_for(escalated);
             notif.show().unwrap();
         });
     } else if args.notifications {
-        notifications::listen(notif_rx, |notif| {
+        notifications::listen(notif_rx, |notif, _| {
             notif.show().unwrap();
         });
     } else if args.beep {
-        notifications::listen(notif_rx, move |_| {
-            beep(&args.beep_path, args.beep_volume);
+        notifications::listen(notif_rx, move |_, escalated| {
+            beep_for(escalated);
         });
     }
 
+    for rule in &ignored_rules {
+        println!(
+            "{}",
+            format!(
+                "WARNING: ignoring {} because it has no conditions. Run `fmrl rules lint` to check your rules.",
+                rule.label()
+            )
+            .black()
+            .on_yellow()
+        );
+    }
+    let now = Local::now();
+    for rule in args.error_rules.iter().filter(|rule| rule.expired(now)) {
+        let expired = rule.expires_at().unwrap_or(now);
+        println!(
+            "{}",
+            format!(
+                "REMINDER: {} expired {} and is ignored. Remove it from your rules.",
+                rule.label(),
+                expired.format("%Y-%m-%d %H:%M")
+            )
+            .black()
+            .on_yellow()
+        );
+    }
+
     // if quiet_errors is specified, print a warning. make it black on yellow
     if !args.quiet_errors.is_empty() {
         println!(
@@ -453,149 +745,91 @@ pub fn run() -> CustomResult {
             .unwrap();
     }
 
-    // when warnings_only or errors_only is true, we only want to print seps if a warning/error occurred, otherwise you get seps even when no text is printed
-    // store this state outside the closure, and have the closure queue up a sep but don't print until a warning/error occurs
-    fn print_separator() {
-        println!("-----------------------------------------------------------------");
+    let filter = LineFilter::new(&args, &config).map_err(|e| format!("invalid filter: {}", e))?;
+    let mut processor = LineProcessor::new(&args);
+    let mut log_file = LogFile::open(path)?;
+
+    if args.tui {
+        return tui::run(tui::TuiOptions {
+            path,
+            log_file,
+            processor,
+            styles,
+            highlighter,
+            filter,
+            watch: !args.no_watch,
+            notif_tx,
+            send_notif: args.notifications || args.beep,
+            color: color_support != ColorSupport::None,
+        });
     }
-    let mut print_sep_on_warning = false;
 
-    // closure/fn to handle each line
+    let context = Context {
+        before: args.before_context.or(args.context).unwrap_or_default(),
+        after: args.after_context.or(args.context).unwrap_or_default(),
+        operation: args.operation_context,
+    };
+    let mut printer = Printer::new(
+        styles,
+        highlighter,
+        args.no_color,
+        args.separator,
+        filter,
+        context,
+        (args.dedup || args.dedup_window.is_some()).then(|| Deduper::new(args.dedup_window)),
+    );
+    printer.collapse_ok = args.collapse_ok;
+    printer.rule_labels = args
+        .explain_rules
+        .then(|| args.error_rules.iter().map(ErrorRule::label).collect());
+    let session = Arc::new(Mutex::new(Session::new(
+        printer,
+        args.history_size.unwrap_or(DEFAULT_HISTORY_SIZE),
+    )));
     let mut handle_line = |line: &str, send_notif: bool| {
-        let line = parse_line(line);
-        let show_line = line.is_header()
-            || (args.errors_only && line.is_error())
-            || (args.warnings_only && line.is_warning())
-            || (!args.errors_only && !args.warnings_only);
-        if !show_line {
-            if args.separator && (args.errors_only || args.warnings_only) {
-                // queue up a separator to be printed before the next warning/error
-                print_sep_on_warning = true;
-            }
-            return;
-        };
-        if args.no_color {
-            println!("{}", line.to_string());
-        } else {
-            match line {
-                LineType::Success(line) => {
-                    let res = colorize_columns(
-                        &line,
-                        &timestamp_colorizer,
-                        &filename_colorizer,
-                        &error_colorizer,
-                        &message_colorizer,
-                    );
-                    let [a, b, c, d] = res;
-                    if args.separator && is_operation_start(&line) {
-                        print_separator();
-                    }
-
-                    println!("{}\t{}\t{}\t{}", a, b, c, d);
-                }
-                LineType::Error(line) => {
-                    if print_sep_on_warning {
-                        print_separator();
-                        print_sep_on_warning = false;
-                    }
-                    let (rule_blocks_color, rule_blocks_notif) =
-                        match apply_error_rules(&args.error_rules, &line) {
-                            Some(ErrorRuleAction::Ignore) => (true, true),
-                            Some(ErrorRuleAction::Quiet) => (false, true),
-                            None => (false, false),
-                        };
-                    if rule_blocks_color {
-                        let [a, b, c, d] = colorize_columns(
-                            &line,
-                            &timestamp_colorizer,
-                            &filename_colorizer,
-                            &error_colorizer,
-                            &message_colorizer,
-                        );
-                        println!("{}\t{}\t{}\t{}", a, b, c, d);
-                    } else {
-                        println!(
-                            "{}\t{}\t{}\t{}",
-                            line.timestamp.bright_white().on_red(),
-                            line.filename.bright_white().on_red(),
-                            line.code.bright_white().on_red(),
-                            line.message
-                        );
-                    };
-
-                    if send_notif && !args.quiet_errors.contains(&line.code) && !rule_blocks_notif {
-                        notif_tx.send(NotificationType::Error).unwrap();
-                    }
-                }
-                LineType::Warning(line) => {
-                    if print_sep_on_warning {
-                        print_separator();
-                        print_sep_on_warning = false;
-                    }
-                    println!(
-                        "{}\t{}\t{}\t{}",
-                        line.timestamp.black().on_yellow(),
-                        line.filename.black().on_yellow(),
-                        line.code.black().on_yellow(),
-                        line.message
-                    );
-                    if send_notif {
-                        notif_tx.send(NotificationType::Warning).unwrap();
-                    }
-                }
-                LineType::Header(line) => {
-                    let res = colorize_columns(
-                        &line,
-                        &timestamp_colorizer,
-                        &filename_colorizer,
-                        &error_colorizer,
-                        &message_colorizer,
-                    );
-                    let [a, b, c, d] = res.map(|s| s.underline());
-                    println!("{}\t{}\t{}\t{}", a, b, c, d);
-                }
-                LineType::Other(line) => {
-                    println!("{}", line);
-                }
-            }
+        let mut session = session.lock().unwrap();
+        // commands like `mute 3702 for 30m` add rules
+        session.update_processor(&mut processor);
+        let line = processor.process(line);
+        let notification = session.handle(line);
+        drop(session);
+        if let (true, Some(notification)) = (send_notif, notification) {
+            notif_tx.send(notification).unwrap();
         }
     };
 
-    let file = File::open(path).map_err(|e| format!("couldn't open '{:?}', {}", path, e))?;
-    let mut reader = io::BufReader::new(&file);
-    let mut buf = String::new();
+    #[cfg(unix)]
+    listen_for_interrupt(Arc::clone(&session));
 
     // read the initial file content
-    reader.read_to_string(&mut buf).unwrap();
     // don't send_notif for intitial file content. It might be a ton of old errors and warnings
-    buf.lines().for_each(|line| handle_line(line, false));
+    log_file
+        .read_new()
+        .unwrap()
+        .lines()
+        .for_each(|line| handle_line(line, false));
+    session.lock().unwrap().end_batch();
 
     if args.no_watch {
+        session.lock().unwrap().finish();
         return Ok(());
     }
 
-    let keyboard_handle = listen_for_keyboard_input();
+    let keyboard_handle = listen_for_keyboard_input(Arc::clone(&session));
 
-    let mut pos = buf.len() as u64;
     // Watch the file for changes
-    let (tx, rx) = mpsc::channel();
-    let mut debouncer = new_debouncer(Duration::from_millis(100), None, tx).unwrap();
-    debouncer
-        .watcher()
-        .watch(path, RecursiveMode::NonRecursive)
-        .unwrap();
+    let (_debouncer, rx) = log_file::watch(path);
 
     // Listen for messages passed from the debouncer thread
     for res in rx {
         match res {
             Ok(_) => {
-                reader.seek(SeekFrom::Start(pos)).unwrap();
-                pos = file.metadata().unwrap().len();
-
-                buf.clear();
-                reader.read_to_string(&mut buf).unwrap();
-                buf.lines()
+                log_file
+                    .read_new()
+                    .unwrap()
+                    .lines()
                     .for_each(|line| handle_line(line, args.notifications || args.beep));
+                session.lock().unwrap().end_batch();
             }
             Err(err) => {
                 eprintln!("Error: {:?}", err);
@@ -615,6 +849,8 @@ pub fn run() -> CustomResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rules::{is_operation_end, is_operation_start};
+    use std::io::Read;
 
     #[test]
     fn test_parse_line() {
@@ -746,6 +982,21 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_is_operation_end() {
+        apply_to_each_file(|buf| {
+            let count_operation_end = buf
+                .lines()
+                .map(parse_line)
+                .filter(|r| match r {
+                    LineType::Success(line) => is_operation_end(line),
+                    _ => false,
+                })
+                .count();
+            assert_eq!(count_operation_end, 9);
+        })
+    }
+
     #[test]
     fn avoid_false_positives() {
         let path = PathBuf::from(BASE_PATH).join("false-positives.log");
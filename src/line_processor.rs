@@ -0,0 +1,122 @@
+use crate::error_rule::{apply_error_rules, ErrorRule, RuleActions};
+use crate::notifications::NotificationType;
+use crate::rules::{is_operation_end, is_operation_start};
+use crate::threshold::Thresholds;
+use crate::timestamp::{parse_timestamp, TimestampFormat, TimestampFormatter};
+use crate::{parse_line, Args, LineType};
+use chrono::Local;
+
+/// A parsed log line along with everything the error rules decided about it
+#[derive(Clone)]
+pub(crate) struct ProcessedLine {
+    pub(crate) line: LineType,
+    /// the timestamp as written in the log, before display formatting
+    pub(crate) raw_timestamp: String,
+    pub(crate) actions: RuleActions,
+    pub(crate) operation_start: bool,
+    /// the import operation's completion line
+    pub(crate) operation_end: bool,
+    /// the number of the import operation this line belongs to, counting from 1 since fmrl started reading
+    pub(crate) operation: Option<usize>,
+    /// the notification to send if this line ends up being shown
+    pub(crate) notification: Option<NotificationType>,
+}
+
+/// Turns raw lines into `ProcessedLine`s. This is shared by every output mode so they agree on parsing, rules and notifications.
+pub(crate) struct LineProcessor {
+    timestamp_formatter: TimestampFormatter,
+    error_rules: Vec<ErrorRule>,
+    thresholds: Thresholds,
+    quiet_errors: Vec<String>,
+    operations: usize,
+}
+
+impl LineProcessor {
+    pub(crate) fn new(args: &Args) -> Self {
+        LineProcessor {
+            timestamp_formatter: TimestampFormatter::new(args.timestamp_format.unwrap_or_default()),
+            error_rules: args.error_rules.clone(),
+            thresholds: Thresholds::default(),
+            quiet_errors: args.quiet_errors.clone(),
+            operations: 0,
+        }
+    }
+
+    /// a processor that applies `error_rules` and nothing else, for checking rules against a log
+    pub(crate) fn for_rules(error_rules: Vec<ErrorRule>) -> Self {
+        LineProcessor {
+            timestamp_formatter: TimestampFormatter::new(TimestampFormat::default()),
+            error_rules,
+            thresholds: Thresholds::default(),
+            quiet_errors: vec![],
+            operations: 0,
+        }
+    }
+
+    /// add a rule after the others, e.g. from a command while watching
+    pub(crate) fn add_rule(&mut self, rule: ErrorRule) {
+        self.error_rules.push(rule);
+    }
+
+    /// expire the rules added by `mute <code> for ...`
+    pub(crate) fn end_mutes(&mut self, code: &str) {
+        let now = Local::now();
+        for rule in &mut self.error_rules {
+            rule.end_mute(code, now);
+        }
+    }
+
+    pub(crate) fn process(&mut self, line: &str) -> ProcessedLine {
+        let mut line = parse_line(line);
+        let mut raw_timestamp = String::new();
+        let mut operation_start = false;
+        // format every line, even ones that end up filtered out, so delta timestamps are relative to the previous line in the log, not the previous printed line
+        if let LineType::Success(l) | LineType::Error(l) | LineType::Warning(l) = &mut line {
+            operation_start = is_operation_start(l);
+            raw_timestamp = l.timestamp.clone();
+            l.timestamp = self
+                .timestamp_formatter
+                .format(&l.timestamp, operation_start);
+        }
+
+        // only successful lines can start an operation
+        let operation_start = operation_start && matches!(line, LineType::Success(_));
+        let operation_end = matches!(&line, LineType::Success(l) if is_operation_end(l));
+        if operation_start {
+            self.operations += 1;
+        }
+        let operation = (self.operations > 0).then_some(self.operations);
+
+        let mut actions = apply_error_rules(&self.error_rules, &line);
+        // rules with a threshold depend on the lines before this one
+        self.thresholds.apply(
+            &self.error_rules,
+            &mut actions,
+            parse_timestamp(&raw_timestamp),
+            operation,
+        );
+        let notification = if actions.escalate {
+            Some(NotificationType::Escalation)
+        } else if actions.silences() {
+            None
+        } else {
+            match &line {
+                LineType::Error(l) if !self.quiet_errors.contains(&l.code) => {
+                    Some(NotificationType::Error)
+                }
+                LineType::Warning(_) => Some(NotificationType::Warning),
+                _ => None,
+            }
+        };
+
+        ProcessedLine {
+            operation_start,
+            operation_end,
+            operation,
+            line,
+            raw_timestamp,
+            actions,
+            notification,
+        }
+    }
+}
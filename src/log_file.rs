@@ -0,0 +1,42 @@
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// An open Import.log that remembers how far it has been read
+pub(crate) struct LogFile {
+    reader: BufReader<File>,
+    pos: u64,
+}
+
+impl LogFile {
+    pub(crate) fn open(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("couldn't open '{:?}', {}", path, e))?;
+        Ok(LogFile {
+            reader: BufReader::new(file),
+            pos: 0,
+        })
+    }
+
+    /// read everything appended since the last call
+    pub(crate) fn read_new(&mut self) -> io::Result<String> {
+        let mut buf = String::new();
+        self.reader.seek(SeekFrom::Start(self.pos))?;
+        self.pos += self.reader.read_to_string(&mut buf)? as u64;
+        Ok(buf)
+    }
+}
+
+/// Watch `path` for changes. Keep the returned debouncer alive for as long as events are needed.
+pub(crate) fn watch(path: &Path) -> (Debouncer<RecommendedWatcher>, Receiver<DebounceEventResult>) {
+    let (tx, rx) = mpsc::channel();
+    let mut debouncer = new_debouncer(Duration::from_millis(100), None, tx).unwrap();
+    debouncer
+        .watcher()
+        .watch(path, RecursiveMode::NonRecursive)
+        .unwrap();
+    (debouncer, rx)
+}
@@ -1,23 +1,19 @@
+use crate::utils::get_s;
 use notify_rust::Notification;
 use std::sync::mpsc::Receiver;
 use std::thread;
 use std::time::{Duration, Instant};
 
+#[derive(Clone, Copy)]
 pub(crate) enum NotificationType {
     Error,
     Warning,
+    /// an error matched an escalate rule. Sent right away, with its own sound
+    Escalation,
     // using 'static since I don't send anything dynamic yet. Can be changed to &'a str or String if needed
     QuickWarning(&'static str),
 }
 
-fn get_s(n: usize) -> &'static str {
-    if n == 1 {
-        ""
-    } else {
-        "s"
-    }
-}
-
 fn create_notification(error_count: usize, warning_count: usize) -> Notification {
     let summary = if error_count > 0 {
         "❌ fmrl Errors 🌈"
@@ -43,7 +39,7 @@ fn create_notification(error_count: usize, warning_count: usize) -> Notification
 
 fn process_messages(
     logs_rx: Receiver<NotificationType>,
-    notification_sender: &dyn Fn(Notification),
+    notification_sender: &dyn Fn(Notification, bool),
 ) {
     let debounce_interval = Duration::from_millis(500);
     let mut last_processed_time = Instant::now();
@@ -54,7 +50,7 @@ fn process_messages(
         if elapsed_time >= debounce_interval {
             if warning_count > 0 || error_count > 0 {
                 let notification = create_notification(error_count, warning_count);
-                notification_sender(notification);
+                notification_sender(notification, false);
                 warning_count = 0;
                 error_count = 0;
             }
@@ -67,10 +63,18 @@ fn process_messages(
                     // immediately show message without incrementing warning_count
                     let notification = Notification::new()
                         .summary("⚠️ fmrl Warning 🌈")
-                        .body(&msg)
+                        .body(msg)
                         .finalize();
                     // send via notification_sender instead of calling .show() directly because notification_sender obeys the configured beep and desktop notification settings passed in via config
-                    notification_sender(notification);
+                    notification_sender(notification, false);
+                }
+                NotificationType::Escalation => {
+                    // don't wait for the batch, and don't count it there either
+                    let notification = Notification::new()
+                        .summary("🚨 fmrl Escalated Error 🌈")
+                        .body("An error matched an escalate rule")
+                        .finalize();
+                    notification_sender(notification, true);
                 }
             }
             // Reset timer in case multiple items are pasted in quick succession
@@ -81,9 +85,10 @@ fn process_messages(
     }
 }
 
+/// `notification_sender` gets each notification, and whether it's an escalation so it can play a different sound
 pub(crate) fn listen<F>(notif_rx: Receiver<NotificationType>, notification_sender: F)
 where
-    F: Fn(Notification) + Send + 'static,
+    F: Fn(Notification, bool) + Send + 'static,
 {
     thread::spawn(move || {
         process_messages(notif_rx, &notification_sender);
@@ -127,7 +132,7 @@ mod tests {
         let (desktop_notifs_tx, desktop_notifs_rx) = mpsc::channel();
 
         // mock_sender used so we don't actually send desktop notifications while testing
-        let mock_sender = move |n| desktop_notifs_tx.send(n).unwrap();
+        let mock_sender = move |n, _| desktop_notifs_tx.send(n).unwrap();
         // we still must listen for and process messages in a separate thread or else we'll block
         thread::spawn(move || {
             process_messages(msg_rx, &mock_sender);
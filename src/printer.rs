@@ -0,0 +1,610 @@
+use crate::dedup::{Deduper, RepeatGroup};
+use crate::error_rule::RuleActions;
+use crate::filter::LineFilter;
+use crate::highlight::Highlighter;
+use crate::line_processor::ProcessedLine;
+use crate::style::LineStyles;
+use crate::LineType;
+use colored::Colorize;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::IsTerminal;
+
+/// Prints processed lines to stdout
+pub(crate) struct Printer {
+    pub(crate) styles: LineStyles,
+    pub(crate) highlighter: Highlighter,
+    pub(crate) no_color: bool,
+    /// print a separator before each import operation
+    pub(crate) separator: bool,
+    pub(crate) filter: LineFilter,
+    /// hold back each import operation until it completes, and print clean ones as a single line
+    pub(crate) collapse_ok: bool,
+    /// how to refer to each error rule, when --explain-rules is on
+    pub(crate) rule_labels: Option<Vec<String>>,
+    /// the lines of the current import operation, while collapse_ok holds them back
+    held_operation: Option<Vec<ProcessedLine>>,
+    // when warnings_only or errors_only is true, we only want to print seps if a warning/error occurred, otherwise you get seps even when no text is printed
+    // so queue up a sep but don't print until a warning/error occurs
+    print_sep_on_warning: bool,
+    context: Context,
+    /// hidden lines that may be printed as context before the next error or warning
+    before: VecDeque<ProcessedLine>,
+    /// how many more hidden lines to print as context after the last error or warning
+    after_left: usize,
+    /// the start of the current import operation, and whether it has been printed
+    operation_start: Option<(ProcessedLine, bool)>,
+    /// hides repeated errors and warnings when --dedup is on
+    dedup: Option<Deduper>,
+    /// groups repeated since the last printed line, shown on the repeat status line
+    repeats: Vec<usize>,
+    /// whether the repeat status line is out of date
+    repeats_changed: bool,
+    /// whether the repeat status line is the last thing printed, so it can be rewritten in place
+    status_printed: bool,
+}
+
+/// Hidden lines to print (dimmed) around shown errors and warnings, like grep's -A/-B/-C
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Context {
+    pub(crate) before: usize,
+    pub(crate) after: usize,
+    /// print the operation's start line before its first error or warning
+    pub(crate) operation: bool,
+}
+
+impl Printer {
+    pub(crate) fn new(
+        styles: LineStyles,
+        highlighter: Highlighter,
+        no_color: bool,
+        separator: bool,
+        filter: LineFilter,
+        context: Context,
+        dedup: Option<Deduper>,
+    ) -> Self {
+        Printer {
+            styles,
+            highlighter,
+            no_color,
+            separator,
+            filter,
+            collapse_ok: false,
+            rule_labels: None,
+            held_operation: None,
+            print_sep_on_warning: false,
+            context,
+            before: VecDeque::new(),
+            after_left: 0,
+            operation_start: None,
+            dedup,
+            repeats: vec![],
+            repeats_changed: false,
+            status_printed: false,
+        }
+    }
+
+    fn print_separator(&mut self) {
+        self.end_status();
+        println!(
+            "{}",
+            self.styles.separator.paint(&self.styles.separator_text)
+        );
+    }
+
+    /// Print a marker line, styled like the separator
+    pub(crate) fn print_marker(&mut self, text: &str) {
+        self.end_status();
+        if self.no_color {
+            println!("{}", text);
+        } else {
+            println!("{}", self.styles.separator.bold().paint(text));
+        }
+    }
+
+    /// Print `line` regardless of the filter, without separators
+    pub(crate) fn print_line(&mut self, line: &ProcessedLine) {
+        self.paint_line(line, false);
+    }
+
+    fn paint_line(&mut self, line: &ProcessedLine, dimmed: bool) {
+        self.end_status();
+        let tags = line.actions.tag_text();
+        let explanation = self.explanation(&line.actions);
+        if self.no_color {
+            println!("{}{}{}", line.line, tags, explanation);
+            return;
+        }
+        // only paint what's there, so lines without them are unchanged
+        let suffix = match (tags.is_empty(), explanation.is_empty()) {
+            (true, true) => String::new(),
+            (false, true) => tags.bold().to_string(),
+            (true, false) => explanation.dimmed().to_string(),
+            (false, false) => format!("{}{}", tags.bold(), explanation.dimmed()),
+        };
+        match (&line.line, self.styles.for_line(&line.line, &line.actions)) {
+            (
+                LineType::Success(l)
+                | LineType::Error(l)
+                | LineType::Warning(l)
+                | LineType::Header(l),
+                Some(style),
+            ) => {
+                let style = match &line.actions.highlight {
+                    Some(highlight) => Cow::Owned(style.highlighted(highlight)),
+                    None => Cow::Borrowed(style),
+                };
+                let [a, b, c, d] = if dimmed {
+                    style.dimmed().paint(l, &self.highlighter)
+                } else {
+                    style.paint(l, &self.highlighter)
+                };
+                println!("{}\t{}\t{}\t{}{}", a, b, c, d, suffix);
+            }
+            (line, _) => {
+                let style = if dimmed {
+                    self.styles.other.dimmed()
+                } else {
+                    self.styles.other.clone()
+                };
+                println!(
+                    "{}{}",
+                    self.highlighter.paint(&line.to_string(), &style),
+                    suffix
+                );
+            }
+        }
+    }
+
+    /// with --explain-rules, the rules that matched the line, e.g. `  ← error_rules[0], error_rules[3] "noisy 114s"`
+    fn explanation(&self, actions: &RuleActions) -> String {
+        match &self.rule_labels {
+            Some(labels) if !actions.rules.is_empty() => {
+                let labels: Vec<&str> = actions.rules.iter().map(|&i| labels[i].as_str()).collect();
+                format!("  ← {}", labels.join(", "))
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn print_context(&mut self, line: &ProcessedLine) {
+        if line.operation_start {
+            if let Some((_, printed)) = &mut self.operation_start {
+                *printed = true;
+            }
+        }
+        self.paint_line(line, true);
+    }
+
+    /// the hidden lines to print before a shown error or warning
+    fn take_context(&mut self, line: &ProcessedLine) -> Vec<ProcessedLine> {
+        let mut context: Vec<ProcessedLine> = self.before.drain(..).collect();
+        if let Some((start, false)) = &self.operation_start {
+            let in_context = context.iter().any(|l| l.operation_start);
+            if self.context.operation && start.operation == line.operation && !in_context {
+                context.insert(0, start.clone());
+            }
+        }
+        context
+    }
+
+    /// Print `line` if the filter shows it, or hold it back with the rest of its import operation when collapse_ok is on.
+    /// Returns whether it was (or will be) shown.
+    pub(crate) fn print(&mut self, line: &ProcessedLine) -> bool {
+        if !self.collapse_ok {
+            return self.print_now(line);
+        }
+        if line.operation_start {
+            // an operation that never completed, like the "Import operations started" wrapper, is printed as is
+            self.release_operation();
+            self.held_operation = Some(vec![line.clone()]);
+            return self.filter.shows(line);
+        }
+        let Some(held) = &mut self.held_operation else {
+            return self.print_now(line);
+        };
+        held.push(line.clone());
+        if line.operation_end {
+            let lines = self.held_operation.take().unwrap_or_default();
+            let has_issue = lines.iter().any(|l| {
+                (l.line.is_warning() || l.line.is_error())
+                    && !l.actions.ignore
+                    && !l.actions.suppress
+            });
+            if has_issue {
+                for line in &lines {
+                    self.print_now(line);
+                }
+            } else {
+                self.print_operation_ok(&lines);
+            }
+        }
+        self.filter.shows(line)
+    }
+
+    /// Print the held back lines of an unfinished import operation. Call when no more lines are coming.
+    pub(crate) fn release_operation(&mut self) {
+        for line in self.held_operation.take().unwrap_or_default() {
+            self.print_now(&line);
+        }
+    }
+
+    /// the single line printed for an import operation without errors or warnings. It stands in for the start of the operation, so it's only shown when the filter shows that
+    fn operation_ok_text(&self, lines: &[ProcessedLine]) -> Option<String> {
+        let LineType::Success(start) = &lines[0].line else {
+            return None;
+        };
+        if !self.filter.shows(&lines[0]) {
+            return None;
+        }
+        let summary = format!("✔ operation OK ({} lines)", lines.len());
+        if self.no_color {
+            return Some(format!(
+                "{}\t{}\t{}",
+                start.timestamp, summary, start.message
+            ));
+        }
+        let style = &self.styles.success;
+        Some(format!(
+            "{}\t{}\t{}",
+            style.timestamp.paint(&start.timestamp),
+            style.filename.paint(&summary),
+            style.message.paint(&start.message)
+        ))
+    }
+
+    fn print_operation_ok(&mut self, lines: &[ProcessedLine]) {
+        let Some(text) = self.operation_ok_text(lines) else {
+            // like a hidden operation start
+            if self.separator {
+                self.print_sep_on_warning = true;
+            }
+            return;
+        };
+        if self.separator && !self.no_color {
+            self.print_separator();
+            self.print_sep_on_warning = false;
+        }
+        self.end_status();
+        println!("{text}");
+    }
+
+    fn print_now(&mut self, line: &ProcessedLine) -> bool {
+        if line.operation_start {
+            self.operation_start = Some((line.clone(), false));
+        }
+        if line.actions.suppress {
+            // not even as context
+            return false;
+        }
+        if !self.filter.shows(line) {
+            if self.after_left > 0 {
+                self.after_left -= 1;
+                self.print_context(line);
+                return false;
+            }
+            if self.context.before > 0 {
+                if self.before.len() == self.context.before {
+                    self.before.pop_front();
+                }
+                self.before.push_back(line.clone());
+            }
+            // queue up a separator to be printed before the next shown line. Type filters always have, patterns only when they hide the start of an operation
+            if self.separator && (self.filter.is_type_active() || line.operation_start) {
+                self.print_sep_on_warning = true;
+            }
+            return false;
+        }
+
+        if let Some(group) = self.dedup.as_mut().and_then(|d| d.check(line)) {
+            if !self.repeats.contains(&group) {
+                self.repeats.push(group);
+            }
+            self.repeats_changed = true;
+            // collapsed into the status line, so no notification either. Escalations still get through
+            return false;
+        }
+
+        let is_issue = line.line.is_error() || line.line.is_warning();
+        // context only makes sense when the filter hides lines
+        let context = if is_issue && self.filter.is_active() {
+            self.take_context(line)
+        } else {
+            self.before.clear();
+            vec![]
+        };
+
+        if !self.no_color {
+            let queued_sep = self.print_sep_on_warning
+                && if self.filter.is_type_active() {
+                    is_issue
+                } else {
+                    !line.line.is_header()
+                };
+            let starts_operation =
+                line.operation_start || context.first().is_some_and(|l| l.operation_start);
+            if (self.separator && starts_operation) || queued_sep {
+                self.print_separator();
+                self.print_sep_on_warning = false;
+            }
+        }
+        for context_line in &context {
+            self.print_context(context_line);
+        }
+        if line.operation_start {
+            self.operation_start = Some((line.clone(), true));
+        }
+        self.print_line(line);
+        if is_issue && self.filter.is_active() {
+            self.after_left = self.context.after;
+        }
+        true
+    }
+
+    /// `×N [code] message` for a repeat group, with the message shortened
+    fn repeat_text(group: &RepeatGroup) -> String {
+        let message = match &group.first.line {
+            LineType::Error(l) | LineType::Warning(l) => l.message.as_str(),
+            _ => "",
+        };
+        let mut short = message.chars().take(40).collect::<String>();
+        if short.len() < message.len() {
+            short.push('…');
+        }
+        format!("×{} [{}] {}", group.count(), group.code(), short)
+    }
+
+    /// Bring the repeat status line up to date. Call after each batch of lines.
+    /// On a terminal it's rewritten in place while nothing else has been printed since.
+    pub(crate) fn print_repeat_status(&mut self) {
+        let Some(dedup) = &self.dedup else {
+            return;
+        };
+        if !self.repeats_changed {
+            return;
+        }
+        const MAX_SHOWN: usize = 3;
+        let mut parts = self
+            .repeats
+            .iter()
+            .take(MAX_SHOWN)
+            .filter_map(|&id| dedup.group(id).map(Self::repeat_text))
+            .collect::<Vec<_>>();
+        if self.repeats.len() > MAX_SHOWN {
+            parts.push(format!("+{} more", self.repeats.len() - MAX_SHOWN));
+        }
+        let text = format!("  ↻ repeated {}", parts.join("  ·  "));
+        if self.status_printed && std::io::stdout().is_terminal() {
+            // move up onto the old status line and clear it
+            print!("\x1b[1A\x1b[2K");
+        }
+        if self.no_color {
+            println!("{}", text);
+        } else {
+            println!("{}", text.dimmed());
+        }
+        self.repeats_changed = false;
+        self.status_printed = true;
+    }
+
+    /// Finish the repeat status line before printing anything else, so later repeats start a new one
+    pub(crate) fn end_status(&mut self) {
+        self.print_repeat_status();
+        self.repeats.clear();
+        self.status_printed = false;
+    }
+
+    /// Print each repeat group: the first line, then its hidden repeats dimmed
+    pub(crate) fn print_repeats(&mut self) {
+        self.end_status();
+        let Some(dedup) = self.dedup.take() else {
+            println!("dedup is off, start with --dedup to group repeated errors and warnings");
+            return;
+        };
+        let mut groups = 0;
+        for group in dedup.repeated() {
+            groups += 1;
+            println!("{}", Self::repeat_text(group));
+            self.paint_line(&group.first, false);
+            for line in &group.repeats {
+                self.paint_line(line, true);
+            }
+            let not_kept = group.count() - 1 - group.repeats.len();
+            if not_kept > 0 {
+                println!("  … {not_kept} more");
+            }
+        }
+        if groups == 0 {
+            println!("no repeated errors or warnings so far");
+        }
+        self.dedup = Some(dedup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_processor::LineProcessor;
+    use crate::Args;
+
+    const LOG: [&str; 5] = [
+        "2023-05-03 08:58:37.211 +0200\tmy.fmp12\t0\tImport of script steps from clipboard started",
+        "2023-05-03 08:58:37.212 +0200\tmy.fmp12\t0\tone",
+        "2023-05-03 08:58:37.213 +0200\tmy.fmp12\t0\ttwo",
+        "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t114\tFile reference missing.",
+        "2023-05-03 08:58:37.215 +0200\tmy.fmp12\t0\tthree",
+    ];
+
+    fn printer(context: Context) -> Printer {
+        let filter = LineFilter {
+            errors_only: true,
+            ..LineFilter::default()
+        };
+        Printer::new(
+            LineStyles::default(),
+            Highlighter::default(),
+            true,
+            false,
+            filter,
+            context,
+            None,
+        )
+    }
+
+    /// feed the log through the printer, returning the messages it kept as before context when the error came in
+    fn context_before_error(printer: &mut Printer) -> Vec<String> {
+        let mut processor = LineProcessor::new(&Args::default());
+        let lines = LOG.iter().map(|l| processor.process(l)).collect::<Vec<_>>();
+        for line in &lines[..3] {
+            printer.print(line);
+        }
+        printer
+            .take_context(&lines[3])
+            .iter()
+            .map(|l| l.line.to_string().rsplit('\t').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_before_context() {
+        let mut p = printer(Context {
+            before: 2,
+            ..Context::default()
+        });
+        assert_eq!(context_before_error(&mut p), ["one", "two"]);
+    }
+
+    #[test]
+    fn operation_context_adds_the_operation_start() {
+        let mut p = printer(Context {
+            before: 1,
+            operation: true,
+            ..Context::default()
+        });
+        assert_eq!(
+            context_before_error(&mut p),
+            ["Import of script steps from clipboard started", "two"]
+        );
+
+        // not repeated when it's already part of the before context
+        let mut p = printer(Context {
+            before: 3,
+            operation: true,
+            ..Context::default()
+        });
+        assert_eq!(context_before_error(&mut p).len(), 3);
+    }
+
+    #[test]
+    fn counts_down_after_context() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut p = printer(Context {
+            after: 1,
+            ..Context::default()
+        });
+        for line in LOG {
+            p.print(&processor.process(line));
+        }
+        assert_eq!(p.after_left, 0);
+        p.print(&processor.process(LOG[3]));
+        assert_eq!(p.after_left, 1);
+    }
+
+    #[test]
+    fn patterns_queue_separators_only_for_hidden_operation_starts() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut p = printer(Context::default());
+        p.separator = true;
+        p.filter = LineFilter {
+            exclude: vec!["one".parse().unwrap()],
+            ..LineFilter::default()
+        };
+        let lines = LOG.map(|l| processor.process(l));
+        p.print(&lines[0]);
+        p.print(&lines[1]);
+        assert!(!p.print_sep_on_warning);
+
+        p.filter = LineFilter {
+            exclude: vec!["started".parse().unwrap()],
+            ..LineFilter::default()
+        };
+        p.print(&lines[0]);
+        assert!(p.print_sep_on_warning);
+    }
+
+    #[test]
+    fn hides_repeats_behind_a_status_line() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut p = printer(Context::default());
+        p.dedup = Some(Deduper::new(None));
+        let lines = LOG.map(|l| processor.process(l));
+        assert!(p.print(&lines[3]));
+        assert!(p.repeats.is_empty());
+        // only counted, and not reported as shown, so repeats don't notify
+        assert!(!p.print(&lines[3]));
+        assert!(!p.print(&lines[3]));
+        assert_eq!(p.repeats, [0]);
+        p.print_repeat_status();
+        assert!(p.status_printed);
+        assert_eq!(
+            Printer::repeat_text(p.dedup.as_ref().unwrap().group(0).unwrap()),
+            "×3 [114] File reference missing."
+        );
+        // printing another line finishes the status line
+        p.print_line(&lines[4]);
+        assert!(p.repeats.is_empty());
+        assert!(!p.status_printed);
+    }
+
+    #[test]
+    fn collapse_ok_holds_back_operations() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut p = printer(Context::default());
+        p.collapse_ok = true;
+        let completed = "2023-05-03 08:58:37.216 +0200\tmy.fmp12\t0\tImport completed";
+        for line in LOG {
+            p.print(&processor.process(line));
+        }
+        assert_eq!(p.held_operation.as_ref().map(Vec::len), Some(5));
+        // the error is still reported as shown while held back
+        assert!(p.print(&processor.process(LOG[3])));
+        p.print(&processor.process(completed));
+        assert!(p.held_operation.is_none());
+
+        p.print(&processor.process(LOG[0]));
+        p.release_operation();
+        assert!(p.held_operation.is_none());
+    }
+
+    #[test]
+    fn collapse_ok_line_follows_the_filter() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let lines = [
+            LOG[0],
+            LOG[1],
+            "2023-05-03 08:58:37.216 +0200\tmy.fmp12\t0\tImport completed",
+        ]
+        .map(|l| processor.process(l));
+        let mut p = printer(Context::default());
+        p.filter = LineFilter::default();
+        assert_eq!(
+            p.operation_ok_text(&lines).as_deref(),
+            Some("2023-05-03 08:58:37.211 +0200\t✔ operation OK (3 lines)\tImport of script steps from clipboard started")
+        );
+
+        // errors only, like printer() sets up
+        let mut p = printer(Context::default());
+        assert_eq!(p.operation_ok_text(&lines), None);
+        p.separator = true;
+        p.print_operation_ok(&lines);
+        assert!(p.print_sep_on_warning);
+
+        p.filter = LineFilter {
+            include: vec!["message:fields".parse().unwrap()],
+            ..LineFilter::default()
+        };
+        assert_eq!(p.operation_ok_text(&lines), None);
+        p.filter.include = vec!["message:script steps".parse().unwrap()];
+        assert!(p.operation_ok_text(&lines).is_some());
+    }
+}
@@ -0,0 +1,496 @@
+use crate::filter::Column;
+use crate::line_processor::ProcessedLine;
+use regex::Regex;
+use std::str::FromStr;
+
+const FIELDS: &str = "timestamp, location, code, message, type, operation, action";
+const TYPES: [&str; 5] = ["error", "warning", "success", "header", "other"];
+const ACTIONS: [&str; 7] = [
+    "quiet",
+    "ignore",
+    "escalate",
+    "suppress",
+    "highlight",
+    "tag",
+    "none",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// a bare word: field names, keywords and unquoted values
+    Word(String),
+    /// a quoted value
+    Str(String),
+    /// one of = == != ~ !~ < <= > >= ( ) ,
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 11] = ["==", "!=", "!~", "<=", ">=", "=", "~", "<", ">", "(", ")"];
+
+/// (token, byte offset in the query)
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, (String, usize)> {
+    let mut tokens = vec![];
+    let mut chars = query.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ',' {
+            chars.next();
+            tokens.push((Token::Symbol(","), pos));
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    // only the quote itself needs escaping, so regexes like "\d+" can be written as is
+                    Some((_, '\\')) if chars.peek().is_some_and(|(_, n)| *n == c) => {
+                        value.push(c);
+                        chars.next();
+                    }
+                    Some((_, q)) if q == c => break,
+                    Some((_, other)) => value.push(other),
+                    None => return Err(("unterminated string".to_string(), pos)),
+                }
+            }
+            tokens.push((Token::Str(value), pos));
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| query[pos..].starts_with(*s)) {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((Token::Symbol(symbol), pos));
+        } else {
+            let mut word = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() || "()=!~<>,\"'".contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.is_empty() {
+                return Err((format!("unexpected `{c}`"), pos));
+            }
+            tokens.push((Token::Word(word), pos));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Column(Column),
+    /// error, warning, success, header or other
+    Type,
+    /// the import operation number
+    Operation,
+    /// the error rule actions: quiet, ignore, escalate, suppress, highlight, tag or none
+    Action,
+}
+
+impl Field {
+    /// every value of the field. Most fields have one, or none when the line doesn't have it, but a line can have several actions
+    fn values(&self, line: &ProcessedLine) -> Vec<String> {
+        match self {
+            Field::Action if line.actions.is_some() => {
+                line.actions.names().into_iter().map(String::from).collect()
+            }
+            Field::Action => vec!["none".to_string()],
+            field => field.value(line).into_iter().collect(),
+        }
+    }
+
+    fn value(&self, line: &ProcessedLine) -> Option<String> {
+        match self {
+            Field::Column(column) => column.value(line).map(String::from),
+            Field::Type => Some(line.line.type_name().to_string()),
+            Field::Operation => line.operation.map(|o| o.to_string()),
+            // lines with several actions use `values`
+            Field::Action => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Eq(String),
+    Ne(String),
+    Contains(String),
+    StartsWith(String),
+    EndsWith(String),
+    Matches(Regex),
+    NotMatches(Regex),
+    In(Vec<String>),
+    Lt(f64),
+    Le(f64),
+    Gt(f64),
+    Ge(f64),
+}
+
+impl Op {
+    fn is_negative(&self) -> bool {
+        matches!(self, Op::Ne(_) | Op::NotMatches(_))
+    }
+
+    fn is_match(&self, value: &str) -> bool {
+        let number = || value.trim().parse::<f64>().ok();
+        match self {
+            Op::Eq(v) => value == v,
+            Op::Ne(v) => value != v,
+            Op::Contains(v) => value.contains(v.as_str()),
+            Op::StartsWith(v) => value.starts_with(v.as_str()),
+            Op::EndsWith(v) => value.ends_with(v.as_str()),
+            Op::Matches(regex) => regex.is_match(value),
+            Op::NotMatches(regex) => !regex.is_match(value),
+            Op::In(values) => values.iter().any(|v| v == value),
+            Op::Lt(n) => number().is_some_and(|v| v < *n),
+            Op::Le(n) => number().is_some_and(|v| v <= *n),
+            Op::Gt(n) => number().is_some_and(|v| v > *n),
+            Op::Ge(n) => number().is_some_and(|v| v >= *n),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op),
+}
+
+impl Expr {
+    fn is_match(&self, line: &ProcessedLine) -> bool {
+        match self {
+            Expr::And(a, b) => a.is_match(line) && b.is_match(line),
+            Expr::Or(a, b) => a.is_match(line) || b.is_match(line),
+            Expr::Not(a) => !a.is_match(line),
+            // fields a line doesn't have (e.g. the code of a line that isn't a log entry) never match
+            Expr::Compare(field, op) => {
+                let values = field.values(line);
+                // with several values, `action = quiet` means any of them is quiet, and `action != quiet` means none is
+                if op.is_negative() {
+                    !values.is_empty() && values.iter().all(|v| op.is_match(v))
+                } else {
+                    values.iter().any(|v| op.is_match(v))
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    query: &'a str,
+}
+
+type ParseResult<T> = Result<T, (String, usize)>;
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(t, _)| t)
+    }
+
+    /// position of the next token, or the end of the query
+    fn pos(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map(|(_, p)| *p)
+            .unwrap_or(self.query.len())
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(t, _)| t.clone());
+        self.next += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> ParseResult<()> {
+        match self.peek() {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.advance();
+                Ok(())
+            }
+            _ => Err((format!("expected `{symbol}`"), self.pos())),
+        }
+    }
+
+    fn or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.and()?;
+        while self.is_keyword("or") {
+            self.advance();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.not()?;
+        while self.is_keyword("and") {
+            self.advance();
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> ParseResult<Expr> {
+        if self.is_keyword("not") {
+            self.advance();
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if let Some(Token::Symbol("(")) = self.peek() {
+            self.advance();
+            let expr = self.or()?;
+            self.expect_symbol(")")?;
+            return Ok(expr);
+        }
+        self.comparison()
+    }
+
+    fn field(&mut self) -> ParseResult<Field> {
+        let pos = self.pos();
+        let Some(Token::Word(word)) = self.advance() else {
+            return Err((format!("expected a field name ({FIELDS})"), pos));
+        };
+        match word.to_lowercase().as_str() {
+            "type" | "severity" => Ok(Field::Type),
+            "operation" => Ok(Field::Operation),
+            "action" => Ok(Field::Action),
+            name => Column::from_str(name).map(Field::Column).map_err(|_| {
+                (
+                    format!("unknown field `{word}`, expected one of {FIELDS}"),
+                    pos,
+                )
+            }),
+        }
+    }
+
+    fn value(&mut self, field: Field) -> ParseResult<String> {
+        let pos = self.pos();
+        let value = match self.advance() {
+            Some(Token::Word(w)) | Some(Token::Str(w)) => w,
+            _ => return Err(("expected a value".to_string(), pos)),
+        };
+        // type and action values are checked up front, so typos don't silently match nothing
+        let allowed: &[&str] = match field {
+            Field::Type => &TYPES,
+            Field::Action => &ACTIONS,
+            _ => return Ok(value),
+        };
+        let value = value.to_lowercase();
+        if !allowed.contains(&value.as_str()) {
+            return Err((
+                format!(
+                    "unknown value `{value}`, expected one of {}",
+                    allowed.join(", ")
+                ),
+                pos,
+            ));
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self, field: Field) -> ParseResult<f64> {
+        let pos = self.pos();
+        let value = self.value(field)?;
+        value
+            .parse()
+            .map_err(|_| (format!("expected a number, got `{value}`"), pos))
+    }
+
+    fn regex(&mut self, field: Field) -> ParseResult<Regex> {
+        let pos = self.pos();
+        let value = self.value(field)?;
+        Regex::new(&value).map_err(|e| (format!("invalid regex: {e}"), pos))
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr> {
+        let field = self.field()?;
+        let pos = self.pos();
+        let op = match self.advance() {
+            Some(Token::Symbol("=" | "==")) => Op::Eq(self.value(field)?),
+            Some(Token::Symbol("!=")) => Op::Ne(self.value(field)?),
+            Some(Token::Symbol("~")) => Op::Matches(self.regex(field)?),
+            Some(Token::Symbol("!~")) => Op::NotMatches(self.regex(field)?),
+            Some(Token::Symbol("<")) => Op::Lt(self.number(field)?),
+            Some(Token::Symbol("<=")) => Op::Le(self.number(field)?),
+            Some(Token::Symbol(">")) => Op::Gt(self.number(field)?),
+            Some(Token::Symbol(">=")) => Op::Ge(self.number(field)?),
+            Some(Token::Word(w)) => match w.to_lowercase().as_str() {
+                "contains" => Op::Contains(self.value(field)?),
+                "starts_with" => Op::StartsWith(self.value(field)?),
+                "ends_with" => Op::EndsWith(self.value(field)?),
+                "matches" => Op::Matches(self.regex(field)?),
+                "in" => {
+                    self.expect_symbol("(")?;
+                    let mut values = vec![self.value(field)?];
+                    while let Some(Token::Symbol(",")) = self.peek() {
+                        self.advance();
+                        values.push(self.value(field)?);
+                    }
+                    self.expect_symbol(")")?;
+                    Op::In(values)
+                }
+                _ => return Err((format!("unknown operator `{w}`"), pos)),
+            },
+            _ => {
+                return Err((
+                    "expected an operator: = != ~ !~ < <= > >= contains starts_with ends_with matches in".to_string(),
+                    pos,
+                ))
+            }
+        };
+        Ok(Expr::Compare(field, op))
+    }
+}
+
+/// A `--where` query, e.g. `code in (114, 3702) and location ~ "Open File" and not message contains "temp"`
+#[derive(Debug, Clone)]
+pub(crate) struct Query(Expr);
+
+impl FromStr for Query {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // point at the problem under the query
+        let error = |(message, pos): (String, usize)| {
+            let column = s[..pos.min(s.len())].chars().count();
+            format!("{message}\n  {s}\n  {}^", " ".repeat(column))
+        };
+        let tokens = tokenize(s).map_err(error)?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            query: s,
+        };
+        if parser.peek().is_none() {
+            return Err("empty query".to_string());
+        }
+        let expr = parser.or().map_err(error)?;
+        if parser.peek().is_some() {
+            return Err(error((
+                "expected `and`, `or` or the end of the query".to_string(),
+                parser.pos(),
+            )));
+        }
+        Ok(Query(expr))
+    }
+}
+
+impl Query {
+    pub(crate) fn is_match(&self, line: &ProcessedLine) -> bool {
+        self.0.is_match(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_rule::ErrorRule;
+    use crate::line_processor::LineProcessor;
+    use crate::Args;
+
+    const LINES: [&str; 4] = [
+        "2023-05-03 08:58:37.211 +0200\tmy.fmp12\t0\tImport of script steps from clipboard started",
+        "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t114\tFile reference “DA9FD4AF” missing.",
+        "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t3702\tSome temp thing",
+        "2023-05-03 08:58:37.215 +0200\tMissing EDS\t0\tscript steps imported : 1",
+    ];
+
+    /// indexes of the lines the query matches
+    fn matching(query: &str) -> Vec<usize> {
+        let args = Args {
+            error_rules: vec![json5::from_str::<ErrorRule>(
+                r#"{ error_code: "3702", action: ["quiet", { tag: "temp" }] }"#,
+            )
+            .unwrap()],
+            ..Args::default()
+        };
+        let mut processor = LineProcessor::new(&args);
+        let query = Query::from_str(query).unwrap();
+        LINES
+            .iter()
+            .map(|l| processor.process(l))
+            .enumerate()
+            .filter(|(_, l)| query.is_match(l))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn example_query() {
+        assert_eq!(
+            matching(
+                r#"code in (114, 3702) and location ~ "Open File" and not message contains "temp""#
+            ),
+            [1]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(matching("code = 0"), [0, 3]);
+        assert_eq!(matching("code != 0"), [1, 2]);
+        assert_eq!(matching("code >= 114"), [1, 2]);
+        assert_eq!(matching("message starts_with 'Some'"), [2]);
+        assert_eq!(matching("message ends_with missing."), [1]);
+        assert_eq!(matching(r#"location !~ "::\d+::""#), [0, 3]);
+        assert_eq!(matching("timestamp contains 37.214"), [1, 2]);
+    }
+
+    #[test]
+    fn precedence_and_grouping() {
+        // and binds tighter than or
+        assert_eq!(
+            matching("code = 114 or code = 0 and location = my.fmp12"),
+            [0, 1]
+        );
+        assert_eq!(
+            matching("(code = 114 or code = 0) and location = my.fmp12"),
+            [0]
+        );
+        assert_eq!(matching("not not code = 114"), [1]);
+    }
+
+    #[test]
+    fn derived_fields() {
+        assert_eq!(matching("type = error"), [1, 2]);
+        assert_eq!(matching("severity = ERROR and action = none"), [1]);
+        assert_eq!(matching("action = quiet"), [2]);
+        assert_eq!(matching("action = tag and type = error"), [2]);
+        // with several actions, != means none of them
+        assert_eq!(matching("action != tag and type = error"), [1]);
+        assert_eq!(matching("operation = 1"), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let err = Query::from_str("code in 114").unwrap_err();
+        assert!(err.starts_with("expected `(`"));
+        assert!(err.ends_with("\n  code in 114\n          ^"));
+
+        assert!(Query::from_str("colour = red")
+            .unwrap_err()
+            .starts_with("unknown field `colour`"));
+        assert!(Query::from_str("type = eror")
+            .unwrap_err()
+            .starts_with("unknown value `eror`"));
+        assert!(Query::from_str("code > abc")
+            .unwrap_err()
+            .starts_with("expected a number"));
+        assert!(Query::from_str("message ~ '(x'")
+            .unwrap_err()
+            .starts_with("invalid regex"));
+        assert!(Query::from_str("message contains 'x")
+            .unwrap_err()
+            .starts_with("unterminated string"));
+        assert!(Query::from_str("code = 1 code = 2")
+            .unwrap_err()
+            .starts_with("expected `and`"));
+        assert!(Query::from_str("(code = 1").is_err());
+        assert!(Query::from_str("  ").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::config_file::get_config;
+use crate::error_rule::ErrorRule;
+use crate::line_processor::LineProcessor;
+use crate::log_file::LogFile;
+use crate::utils::get_s;
+use crate::CustomResult;
+use chrono::Local;
+use std::path::Path;
+
+/// Everything that looks wrong with `rules`, one line per rule, in order. With a `sample` log, rules that match none of its lines are reported too.
+pub(crate) fn lint_rules(rules: &[ErrorRule], sample: Option<&str>) -> Vec<String> {
+    // rules without conditions are ignored when watching, so leave them out of the sample too
+    let used: Vec<ErrorRule> = rules
+        .iter()
+        .filter(|rule| !rule.no_match_logic())
+        .cloned()
+        .collect();
+    let hits = sample.map(|log| {
+        let mut hits = vec![0; used.len()];
+        let mut processor = LineProcessor::for_rules(used.clone());
+        for line in log.lines() {
+            for i in processor.process(line).actions.rules {
+                hits[i] += 1;
+            }
+        }
+        hits
+    });
+
+    let mut problems = vec![];
+    let mut used_i = 0;
+    let now = Local::now();
+    for (i, rule) in rules.iter().enumerate() {
+        let label = rule.label();
+        if rule.no_match_logic() {
+            problems.push(format!(
+                "{label} has no conditions, so it's ignored. Add a condition or a line_type"
+            ));
+            continue;
+        }
+        let hit_count = hits.as_ref().map(|hits| hits[used_i]);
+        used_i += 1;
+        if let Some(at) = rule.expires_at().filter(|_| rule.expired(now)) {
+            problems.push(format!(
+                "{label} expired {}, so it's ignored. Remove it from the config",
+                at.format("%Y-%m-%d %H:%M")
+            ));
+            continue;
+        }
+
+        let earlier = rules[..i].iter().filter(|e| !e.no_match_logic());
+        if let Some(earlier) = earlier.clone().find(|e| e.same_as(rule)) {
+            problems.push(format!("{label} duplicates {}", earlier.label()));
+        } else if let Some(earlier) = earlier.clone().find(|e| e.shadows(rule)) {
+            problems.push(format!(
+                "{label} is shadowed by {}, which matches every line it does and already does everything it does",
+                earlier.label()
+            ));
+        } else if let Some(why) = rule.contradiction() {
+            problems.push(format!("{label} is contradictory: {why}"));
+        } else if hit_count == Some(0) {
+            problems.push(format!("{label} matched nothing in the sample log"));
+        }
+    }
+    problems
+}
+
+/// `fmrl rules lint`: print the problems with the config's error rules, and exit with 1 if there are any
+pub(crate) fn run(config_path: Option<&str>, sample: Option<&Path>) -> CustomResult {
+    let config = get_config(config_path)?;
+    let rules = &config.error_rules;
+    if rules.is_empty() {
+        println!("no error_rules to lint");
+        return Ok(());
+    }
+    let sample = match sample {
+        Some(path) => Some(LogFile::open(path)?.read_new()?),
+        None => None,
+    };
+
+    let problems = lint_rules(rules, sample.as_deref());
+    if problems.is_empty() {
+        println!(
+            "{} error rule{}, no problems found",
+            rules.len(),
+            get_s(rules.len())
+        );
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("{problem}");
+    }
+    println!(
+        "{} error rule{}, {} problem{}",
+        rules.len(),
+        get_s(rules.len()),
+        problems.len(),
+        get_s(problems.len())
+    );
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_rule::deserialize_error_rules;
+
+    fn lint(json: &str, sample: Option<&str>) -> Vec<String> {
+        let rules =
+            deserialize_error_rules(&mut json5::Deserializer::from_str(json).unwrap()).unwrap();
+        lint_rules(&rules, sample)
+    }
+
+    #[test]
+    fn reports_empty_duplicate_and_shadowed_rules() {
+        let problems = lint(
+            r#"[
+                { "action": "quiet" },
+                { "name": "missing refs", "error_code": 114, "action": "ignore" },
+                { "error_code": 114, "action": "ignore" },
+                { "error_code": 114, "message_starts_with": "File reference", "action": "quiet" },
+                // a tag adds something, so it isn't shadowed
+                { "error_code": 114, "message_contains": "missing", "action": { "tag": "missing" } },
+                { "error_code": 114, "any_of": [{ "message_contains": "a" }, { "message_contains": "b" }], "action": "ignore" },
+            ]"#,
+            None,
+        );
+        assert_eq!(
+            problems,
+            [
+                "error_rules[0] has no conditions, so it's ignored. Add a condition or a line_type",
+                r#"error_rules[2] duplicates error_rules[1] "missing refs""#,
+                r#"error_rules[3] is shadowed by error_rules[1] "missing refs", which matches every line it does and already does everything it does"#,
+                r#"error_rules[5] is shadowed by error_rules[1] "missing refs", which matches every line it does and already does everything it does"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn narrower_rules_are_not_shadows() {
+        let problems = lint(
+            r#"[
+                { "error_code": 114, "message_starts_with": "File", "action": "ignore" },
+                { "error_code": 114, "action": "ignore" },
+                { "message_contains": "missing", "action": "quiet" },
+                { "message_contains": "missing", "not": { "location_contains": "Prod" }, "action": "quiet" },
+                { "message_contains": "missing", "not": { "location_contains": "Production" }, "action": "ignore" },
+            ]"#,
+            None,
+        );
+        assert_eq!(
+            problems,
+            [
+                r#"error_rules[3] is shadowed by error_rules[2], which matches every line it does and already does everything it does"#
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_contradictory_rules() {
+        let problems = lint(
+            r#"[
+                { "error_code": 0, "action": "quiet" },
+                { "line_type": "warning", "error_code": 114, "action": "quiet" },
+                { "message_contains": "missing", "not": { "message_contains": "miss" }, "action": "quiet" },
+                { "error_code": 1, "action": ["ignore", "escalate"] },
+                { "error_code": 2, "action": ["suppress", { "tag": "hidden" }] },
+                { "line_type": "success", "error_code": 0, "action": { "tag": "ok" } },
+            ]"#,
+            None,
+        );
+        assert_eq!(
+            problems,
+            [
+                "error_rules[0] is contradictory: error_code 0 never occurs on error lines",
+                "error_rules[1] is contradictory: error_code 114 never occurs on warning lines",
+                "error_rules[2] is contradictory: its `not` excludes every line the rest of it matches",
+                "error_rules[3] is contradictory: it both ignores and escalates",
+                "error_rules[4] is contradictory: it suppresses the lines it would tag",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_expired_rules() {
+        let problems = lint(
+            r#"[
+                { "error_code": 114, "expires": "2020-01-02 17:30", "action": "quiet" },
+                { "error_code": 3702, "expires": "2999-01-01", "action": "quiet" },
+            ]"#,
+            None,
+        );
+        assert_eq!(
+            problems,
+            ["error_rules[0] expired 2020-01-02 17:30, so it's ignored. Remove it from the config"]
+        );
+    }
+
+    #[test]
+    fn reports_rules_that_match_nothing_in_the_sample() {
+        let sample = include_str!("../tests/inputs/en-Import.log");
+        let problems = lint(
+            r#"[
+                { "error_code": 114, "action": "quiet" },
+                { "error_code": 3702, "action": "quiet" },
+                { "line_type": "warning", "message_contains": "created and imported automatically", "action": "quiet" },
+            ]"#,
+            Some(sample),
+        );
+        assert_eq!(
+            problems,
+            ["error_rules[1] matched nothing in the sample log"]
+        );
+    }
+}
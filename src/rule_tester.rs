@@ -0,0 +1,273 @@
+use crate::config_file::get_config;
+use crate::error_rule::{remove_no_match_rules, ErrorRule, RuleActions};
+use crate::line_processor::{LineProcessor, ProcessedLine};
+use crate::log_file::LogFile;
+use crate::utils::get_s;
+use crate::{CustomResult, LineType};
+use colored::Colorize;
+use serde::Deserialize;
+use std::fmt;
+use std::path::Path;
+
+/// What the rules should do with one line of the input, from an expectation file
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Expectation {
+    /// counting from 1
+    line: usize,
+    /// action names, as printed. An empty array (or "none") expects no actions
+    actions: Option<Vec<String>>,
+    /// the rules that should match, by name or position in error_rules
+    rules: Option<Vec<RuleRef>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum RuleRef {
+    Index(usize),
+    Name(String),
+}
+
+impl RuleRef {
+    fn refers_to(&self, rule: &ErrorRule) -> bool {
+        match self {
+            RuleRef::Index(i) => rule.index() == Some(*i),
+            RuleRef::Name(name) => rule.name() == Some(name.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for RuleRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleRef::Index(i) => write!(f, "error_rules[{i}]"),
+            RuleRef::Name(name) => write!(f, "\"{name}\""),
+        }
+    }
+}
+
+impl Expectation {
+    /// how the actual result differs from the expectation
+    fn mismatches(&self, rules: &[ErrorRule], actions: &RuleActions) -> Vec<String> {
+        let mut res = vec![];
+        if let Some(expected) = &self.actions {
+            let mut expected: Vec<&str> = expected
+                .iter()
+                .map(String::as_str)
+                .filter(|a| *a != "none")
+                .collect();
+            let actual = actions.names();
+            let mut sorted = actual.clone();
+            expected.sort_unstable();
+            sorted.sort_unstable();
+            if expected != sorted {
+                res.push(format!(
+                    "expected actions: {}, got: {}",
+                    names_or_none(&expected),
+                    names_or_none(&actual)
+                ));
+            }
+        }
+        if let Some(expected) = &self.rules {
+            let matched: Vec<&ErrorRule> = actions.rules.iter().map(|&i| &rules[i]).collect();
+            let same = expected.len() == matched.len()
+                && expected
+                    .iter()
+                    .all(|r| matched.iter().any(|rule| r.refers_to(rule)));
+            if !same {
+                let expected: Vec<String> = expected.iter().map(RuleRef::to_string).collect();
+                let matched: Vec<String> = matched.iter().map(|rule| rule.label()).collect();
+                res.push(format!(
+                    "expected rules: {}, got: {}",
+                    names_or_none(&expected),
+                    names_or_none(&matched)
+                ));
+            }
+        }
+        res
+    }
+}
+
+fn names_or_none<S: AsRef<str>>(names: &[S]) -> String {
+    if names.is_empty() {
+        return "none".to_string();
+    }
+    names
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// the parsed fields, matching rules and actions of one line
+fn describe(n: usize, line: &LineType, rules: &[ErrorRule], actions: &RuleActions) -> String {
+    let mut res = format!("line {n} ({})", line.type_name());
+    match line {
+        LineType::Other(text) => res.push_str(&format!("\n  text       {text}")),
+        LineType::Success(l) | LineType::Error(l) | LineType::Warning(l) | LineType::Header(l) => {
+            res.push_str(&format!("\n  timestamp  {}", l.timestamp));
+            res.push_str(&format!("\n  location   {}", l.filename));
+            res.push_str(&format!("\n  code       {}", l.code));
+            res.push_str(&format!("\n  message    {}", l.message));
+        }
+    }
+    let matched: Vec<String> = actions.rules.iter().map(|&i| rules[i].label()).collect();
+    res.push_str(&format!("\n  rules      {}", names_or_none(&matched)));
+    res.push_str(&format!(
+        "\n  actions    {}",
+        names_or_none(&actions.names())
+    ));
+    if !actions.tags.is_empty() {
+        res.push_str(&format!(
+            "\n  tags       {}",
+            actions.tag_text().trim_start()
+        ));
+    }
+    res
+}
+
+/// `fmrl rules test`: run the config's error rules over a line or a log and print what they do to each line. Exits with 1 if an expectation fails
+pub(crate) fn run(
+    config_path: Option<&str>,
+    line: Option<&str>,
+    log: Option<&Path>,
+    expect: Option<&Path>,
+) -> CustomResult {
+    let mut config = get_config(config_path)?;
+    // the same rules fmrl uses while watching
+    remove_no_match_rules(&mut config.error_rules);
+    let rules = &config.error_rules;
+    let input = match (line, log) {
+        (Some(line), _) => line.to_string(),
+        (None, Some(path)) => LogFile::open(path)?.read_new()?,
+        (None, None) => return Err("pass a log line or --log".into()),
+    };
+    let expectations: Vec<Expectation> = match expect {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("couldn't read expectation file: {}", e))?;
+            json5::from_str(&text).map_err(|e| format!("couldn't parse expectation file: {}", e))?
+        }
+        None => vec![],
+    };
+
+    let lines: Vec<&str> = input.lines().collect();
+    let mut failed = 0;
+    // rules with a threshold count the lines before each one
+    let mut processor = LineProcessor::for_rules(rules.clone());
+    for (i, raw) in lines.iter().enumerate() {
+        let n = i + 1;
+        let ProcessedLine { line, actions, .. } = processor.process(raw);
+        if !raw.trim().is_empty() {
+            println!("{}", describe(n, &line, rules, &actions));
+        }
+        for expectation in expectations.iter().filter(|e| e.line == n) {
+            for mismatch in expectation.mismatches(rules, &actions) {
+                failed += 1;
+                println!("  {}", format!("✘ {mismatch}").red());
+            }
+        }
+    }
+    for expectation in expectations
+        .iter()
+        .filter(|e| e.line == 0 || e.line > lines.len())
+    {
+        failed += 1;
+        println!(
+            "{}",
+            format!(
+                "✘ expected line {}, but the input has {} line{}",
+                expectation.line,
+                lines.len(),
+                get_s(lines.len())
+            )
+            .red()
+        );
+    }
+
+    if expectations.is_empty() {
+        return Ok(());
+    }
+    if failed > 0 {
+        println!(
+            "{failed} mismatch{} in {} expectation{}",
+            if failed == 1 { "" } else { "es" },
+            expectations.len(),
+            get_s(expectations.len())
+        );
+        std::process::exit(1);
+    }
+    println!(
+        "{} expectation{} met",
+        expectations.len(),
+        get_s(expectations.len())
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_rule::{apply_error_rules, deserialize_error_rules};
+    use crate::parse_line;
+
+    const LINE: &str = "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t114\tFile reference “DA9FD4AF” missing.";
+
+    fn rules() -> Vec<ErrorRule> {
+        let json = r#"[
+            { "name": "missing refs", "error_code": 114, "action": "quiet" },
+            { "message_contains": "missing", "action": { "tag": "missing" } },
+            { "error_code": 3702, "action": "ignore" },
+        ]"#;
+        deserialize_error_rules(&mut json5::Deserializer::from_str(json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn describes_fields_rules_and_actions() {
+        let rules = rules();
+        let line = parse_line(LINE);
+        let actions = apply_error_rules(&rules, &line);
+        assert_eq!(
+            describe(1, &line, &rules, &actions),
+            "line 1 (error)
+  timestamp  2023-05-03 08:58:37.214 +0200
+  location   Missing EDS::2::Open File
+  code       114
+  message    File reference “DA9FD4AF” missing.
+  rules      error_rules[0] \"missing refs\", error_rules[1]
+  actions    quiet, tag
+  tags       [missing]"
+        );
+    }
+
+    #[test]
+    fn expectations_compare_actions_and_rules() {
+        let rules = rules();
+        let actions = apply_error_rules(&rules, &parse_line(LINE));
+        let expect = |json: &str| {
+            json5::from_str::<Expectation>(json)
+                .unwrap()
+                .mismatches(&rules, &actions)
+        };
+
+        assert!(
+            expect(r#"{ line: 1, actions: ["tag", "quiet"], rules: ["missing refs", 1] }"#)
+                .is_empty()
+        );
+        assert_eq!(
+            expect(r#"{ line: 1, actions: ["ignore"] }"#),
+            ["expected actions: ignore, got: quiet, tag"]
+        );
+        assert_eq!(
+            expect(r#"{ line: 1, rules: [2] }"#),
+            [
+                r#"expected rules: error_rules[2], got: error_rules[0] "missing refs", error_rules[1]"#
+            ]
+        );
+
+        let actions = apply_error_rules(&rules, &parse_line("not a log line"));
+        let expectation: Expectation =
+            json5::from_str(r#"{ line: 1, actions: ["none"], rules: [] }"#).unwrap();
+        assert!(expectation.mismatches(&rules, &actions).is_empty());
+    }
+}
@@ -20,4 +20,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.ends_with(" démarrée") || msg.ends_with(" démarrées")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "Importation terminée"
+    }
 }
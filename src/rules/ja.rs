@@ -21,4 +21,8 @@ impl Rules for RulesImpl {
         msg.ends_with(" のインポートを開始しました")
             || msg.ends_with("インポート処理が開始されました")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "インポートが完了しました"
+    }
 }
@@ -23,4 +23,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.ends_with(" 가져오기가 시작됨") || msg.ends_with("가져오기 작업 시작됨")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "가져오기 완료됨"
+    }
 }
@@ -39,6 +39,7 @@ trait Rules {
 
     fn is_header(&self, msg: &str) -> bool;
     fn is_operation_start(&self, msg: &str) -> bool;
+    fn is_operation_end(&self, msg: &str) -> bool;
 
     // this method has a blanket implementation. No need to reimplement.
     fn contains_warning_text(&self, msg: &str) -> bool {
@@ -60,6 +61,12 @@ pub(crate) fn is_operation_start(line: &ImportLogLine) -> bool {
         .any(|rules| rules.is_operation_start(&line.message))
 }
 
+pub(crate) fn is_operation_end(line: &ImportLogLine) -> bool {
+    get_rules_impls()
+        .iter()
+        .any(|rules| rules.is_operation_end(&line.message))
+}
+
 pub(crate) fn is_header(line: &str) -> bool {
     get_rules_impls().iter().any(|rules| rules.is_header(line))
 }
@@ -20,4 +20,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.ends_with(" gestart")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "Import voltooid"
+    }
 }
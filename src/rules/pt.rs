@@ -22,4 +22,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.ends_with(" iniciada") || msg.ends_with(" iniciadas")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "Importação concluída"
+    }
 }
@@ -20,4 +20,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.ends_with(" startats")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "Importen slutförd"
+    }
 }
@@ -20,4 +20,8 @@ impl Rules for RulesImpl {
     fn is_operation_start(&self, msg: &str) -> bool {
         msg.starts_with("开始从剪贴板导") || msg.starts_with("导入操作已开始")
     }
+
+    fn is_operation_end(&self, msg: &str) -> bool {
+        msg == "导入完成"
+    }
 }
@@ -0,0 +1,587 @@
+use crate::commands::{Command, HELP};
+use crate::error_rule::ErrorRule;
+use crate::highlight::search_regex;
+use crate::line_processor::{LineProcessor, ProcessedLine};
+use crate::notifications::NotificationType;
+use crate::printer::Printer;
+use crate::utils::{clear_terminal, get_s};
+use crate::LineType;
+use chrono::Local;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, IsTerminal, Write};
+
+/// Counts of everything read from the log so far
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    lines: usize,
+    operations: usize,
+    errors: usize,
+    /// errors quieted or ignored by error rules
+    rule_errors: usize,
+    warnings: usize,
+    error_codes: BTreeMap<String, usize>,
+    /// lines matched by each error rule, by position in the list of rules
+    rule_hits: BTreeMap<usize, usize>,
+}
+
+impl Stats {
+    fn record(&mut self, line: &ProcessedLine) {
+        self.lines += 1;
+        if line.operation_start {
+            self.operations += 1;
+        }
+        for &rule in &line.actions.rules {
+            *self.rule_hits.entry(rule).or_default() += 1;
+        }
+        match &line.line {
+            LineType::Error(l) => {
+                if line.actions.silences() {
+                    self.rule_errors += 1;
+                } else {
+                    self.errors += 1;
+                }
+                *self.error_codes.entry(l.code.clone()).or_default() += 1;
+            }
+            LineType::Warning(_) => self.warnings += 1,
+            _ => {}
+        }
+    }
+
+    fn counts(&self) -> Counts {
+        Counts {
+            lines: self.lines,
+            operations: self.operations,
+            errors: self.errors + self.rule_errors,
+            warnings: self.warnings,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut res = format!(
+            "{} lines, {} import operations, {} errors ({} more quieted or ignored by rules), {} warnings",
+            self.lines, self.operations, self.errors, self.rule_errors, self.warnings
+        );
+        if !self.error_codes.is_empty() {
+            let codes = self
+                .error_codes
+                .iter()
+                .map(|(code, count)| format!("{code} ×{count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            res.push_str(&format!("\nerror codes: {codes}"));
+        }
+        res
+    }
+}
+
+/// Running totals, to count what came in since a marker or a pause without keeping the lines around
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Counts {
+    lines: usize,
+    operations: usize,
+    /// including the ones quieted or ignored by rules
+    errors: usize,
+    warnings: usize,
+}
+
+impl Counts {
+    fn since(self, earlier: Counts) -> Counts {
+        Counts {
+            lines: self.lines - earlier.lines,
+            operations: self.operations - earlier.operations,
+            errors: self.errors - earlier.errors,
+            warnings: self.warnings - earlier.warnings,
+        }
+    }
+}
+
+/// How many lines `history` keeps by default
+pub(crate) const DEFAULT_HISTORY_SIZE: usize = 10_000;
+
+/// State shared between the line handler and the keyboard thread. Keep it behind a mutex so commands apply between lines, never in the middle of one.
+pub(crate) struct Session {
+    printer: Printer,
+    stats: Stats,
+    /// error codes muted at runtime
+    muted: Vec<String>,
+    /// rules added by commands, for the line processor to pick up
+    new_rules: Vec<ErrorRule>,
+    /// codes unmuted since, whose timed mutes the line processor should end
+    unmuted: Vec<String>,
+    /// the last `history_size` lines read, for searching and resuming
+    history: VecDeque<ProcessedLine>,
+    history_size: usize,
+    /// the counts at the last marker
+    last_marker: Option<Counts>,
+    /// while paused, the counts when the pause started. Lines after it haven't been printed yet
+    paused_at: Option<Counts>,
+}
+
+impl Session {
+    pub(crate) fn new(printer: Printer, history_size: usize) -> Self {
+        Session {
+            printer,
+            stats: Stats::default(),
+            muted: vec![],
+            new_rules: vec![],
+            unmuted: vec![],
+            history: VecDeque::new(),
+            history_size,
+            last_marker: None,
+            paused_at: None,
+        }
+    }
+
+    /// Print (or buffer, while paused) a line. Returns the notification to send for it, if any.
+    pub(crate) fn handle(&mut self, line: ProcessedLine) -> Option<NotificationType> {
+        self.stats.record(&line);
+        let notification = match (&line.line, line.notification) {
+            (LineType::Error(l), Some(NotificationType::Error)) if self.muted.contains(&l.code) => {
+                None
+            }
+            (_, notification) => notification,
+        };
+        let shown = if self.paused_at.is_some() {
+            self.printer.filter.shows(&line)
+        } else {
+            self.printer.print(&line)
+        };
+        if self.history.len() == self.history_size {
+            self.history.pop_front();
+        }
+        if self.history_size > 0 {
+            self.history.push_back(line);
+        }
+        // escalations get through mutes and filters
+        notification.filter(|n| shown || matches!(n, NotificationType::Escalation))
+    }
+
+    /// Apply the rules added and the timed mutes ended by commands since the last call
+    pub(crate) fn update_processor(&mut self, processor: &mut LineProcessor) {
+        for code in self.unmuted.drain(..) {
+            processor.end_mutes(&code);
+        }
+        for rule in self.new_rules.drain(..) {
+            processor.add_rule(rule);
+        }
+    }
+
+    /// what came in while paused
+    fn pending_counts(&self) -> Counts {
+        match self.paused_at {
+            Some(at) => self.stats.counts().since(at),
+            None => Counts::default(),
+        }
+    }
+
+    /// the lines read while paused that are still in `history`
+    fn pending(&self) -> impl Iterator<Item = &ProcessedLine> {
+        let kept = self.pending_counts().lines.min(self.history.len());
+        self.history.range(self.history.len() - kept..)
+    }
+
+    /// Update the status lines. Call after each batch of lines.
+    pub(crate) fn end_batch(&mut self) {
+        self.printer.print_repeat_status();
+        self.print_pause_status();
+    }
+
+    /// Print anything still held back. Call when no more lines are coming.
+    pub(crate) fn finish(&mut self) {
+        self.printer.release_operation();
+        self.end_batch();
+        self.print_rule_hits();
+    }
+
+    /// with --explain-rules, how many lines each rule matched, including rules that matched nothing
+    fn print_rule_hits(&self) {
+        let Some(labels) = &self.printer.rule_labels else {
+            return;
+        };
+        if labels.is_empty() {
+            return;
+        }
+        let hits: Vec<usize> = (0..labels.len())
+            .map(|i| self.stats.rule_hits.get(&i).copied().unwrap_or_default())
+            .collect();
+        let width = hits.iter().max().unwrap_or(&0).to_string().len();
+        println!("error rule hits:");
+        for (label, hits) in labels.iter().zip(hits) {
+            println!("  {hits:>width$}  {label}");
+        }
+    }
+
+    /// While paused, keep a single line up to date with how much output is waiting. Only on a terminal, where the line can be rewritten
+    fn print_pause_status(&self) {
+        let Counts {
+            lines,
+            errors,
+            warnings,
+            ..
+        } = self.pending_counts();
+        if lines == 0 || !io::stdout().is_terminal() {
+            return;
+        }
+        // \x1b[2K clears the previous status
+        print!(
+            "\r\x1b[2K⏸ paused: {lines} new line{}, {errors} error{}, {warnings} warning{} (resume / resume summary)",
+            get_s(lines),
+            get_s(errors),
+            get_s(warnings)
+        );
+        let _ = io::stdout().flush();
+    }
+
+    fn resume(&mut self, summary: bool) {
+        let Counts {
+            lines,
+            operations,
+            errors,
+            warnings,
+        } = self.pending_counts();
+        if self.paused_at.is_none() {
+            return;
+        }
+        let pending: Vec<ProcessedLine> = self.pending().cloned().collect();
+        self.paused_at = None;
+        if lines > 0 && io::stdout().is_terminal() {
+            // clear the pause status
+            print!("\r\x1b[2K");
+        }
+        if pending.len() < lines {
+            let dropped = lines - pending.len();
+            println!(
+                "{dropped} line{} read while paused {} no longer kept, see --history-size",
+                get_s(dropped),
+                if dropped == 1 { "is" } else { "are" }
+            );
+        }
+        if !summary {
+            for line in &pending {
+                self.printer.print(line);
+            }
+            return;
+        }
+
+        println!(
+            "while paused: {lines} line{}, {operations} import operation{}, {errors} error{}, {warnings} warning{}",
+            get_s(lines),
+            get_s(operations),
+            get_s(errors),
+            get_s(warnings)
+        );
+        // show the errors even if the current filter hides them
+        for line in pending.iter().filter(|l| l.line.is_error()) {
+            self.printer.print_line(line);
+        }
+    }
+
+    /// the marker line, with counts of the errors and warnings since the previous marker (or the start) if `summary` is true
+    fn marker_text(&self, label: Option<&str>, summary: bool) -> String {
+        let Counts {
+            errors, warnings, ..
+        } = self
+            .stats
+            .counts()
+            .since(self.last_marker.unwrap_or_default());
+        let previous = if self.last_marker.is_some() {
+            "previous marker"
+        } else {
+            "start"
+        };
+        let mut text = format!("━━━━ ▶ {}", Local::now().format("%Y-%m-%d %H:%M:%S"));
+        if let Some(label) = label {
+            text.push_str(&format!(" {label}"));
+        }
+        if !summary {
+            text.push_str(" ━━━━");
+            return text;
+        }
+        text.push_str(&format!(
+            " ━━━━ since {previous}: {errors} error{}, {warnings} warning{}",
+            get_s(errors),
+            get_s(warnings)
+        ));
+        text
+    }
+
+    fn search(&mut self, term: String) {
+        let regex = search_regex(&term);
+        self.printer.highlighter.set_search(regex.clone());
+        let Some(regex) = regex else {
+            println!("search cleared");
+            return;
+        };
+        let matches = self
+            .history
+            .iter()
+            .filter(|l| regex.is_match(&l.line.to_string()))
+            .collect::<Vec<_>>();
+        println!(
+            "search \"{term}\": {} matching line{} so far",
+            matches.len(),
+            get_s(matches.len())
+        );
+        for line in matches {
+            self.printer.print_line(line);
+        }
+        println!("search \"{term}\": highlighting new lines, type / to stop");
+    }
+
+    /// Run a command from the keyboard. `Quit` is left to the caller.
+    pub(crate) fn run_command(&mut self, command: Command) {
+        // the typed command has moved the cursor past the repeat status line
+        self.printer.end_status();
+        match command {
+            Command::Quit => {}
+            Command::Clear => clear_terminal(),
+            Command::Errors => self.printer.filter.set_types(true, false),
+            Command::Warnings => self.printer.filter.set_types(false, true),
+            Command::All => self.printer.filter.set_types(false, false),
+            Command::Pause => {
+                if self.paused_at.is_none() {
+                    self.paused_at = Some(self.stats.counts());
+                }
+                println!("paused, type resume to continue");
+            }
+            Command::Resume { summary } => self.resume(summary),
+            Command::Mark { label, summary } => {
+                self.printer
+                    .print_marker(&self.marker_text(label.as_deref(), summary));
+                self.last_marker = Some(self.stats.counts());
+            }
+            Command::Stats => println!("{}", self.stats.summary()),
+            Command::Search(term) => self.search(term),
+            Command::Mute(code) => {
+                if !self.muted.contains(&code) {
+                    self.muted.push(code.clone());
+                }
+                println!("muted error {code}");
+            }
+            Command::Unmute(code) => {
+                self.muted.retain(|c| *c != code);
+                self.unmuted.push(code.clone());
+                println!("unmuted error {code}");
+            }
+            Command::MuteFor(code, duration) => {
+                let Some(until) = Local::now().checked_add_signed(duration) else {
+                    println!("mute: {code} can't be muted that long");
+                    return;
+                };
+                let rule = ErrorRule::mute(&code, until);
+                // rules are explained by position, and this one goes last
+                if let Some(labels) = &mut self.printer.rule_labels {
+                    labels.push(rule.label());
+                }
+                self.new_rules.push(rule);
+                println!("muted error {code} until {}", until.format("%H:%M"));
+            }
+            Command::Expand => self.printer.print_repeats(),
+            Command::Help => println!("{HELP}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dedup::Deduper;
+    use crate::filter::LineFilter;
+    use crate::highlight::Highlighter;
+    use crate::line_processor::LineProcessor;
+    use crate::printer::Context;
+    use crate::style::LineStyles;
+    use crate::Args;
+
+    const ERROR: &str =
+        "2023-05-03 08:58:37.214 +0200\tMissing EDS::2::Open File\t3702\tsomething went wrong";
+
+    fn session() -> Session {
+        let printer = Printer::new(
+            LineStyles::default(),
+            Highlighter::default(),
+            true,
+            false,
+            LineFilter::default(),
+            Context::default(),
+            None,
+        );
+        Session::new(printer, DEFAULT_HISTORY_SIZE)
+    }
+
+    #[test]
+    fn muted_codes_dont_notify() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut session = session();
+        assert!(session.handle(processor.process(ERROR)).is_some());
+        session.run_command(Command::Mute("3702".to_string()));
+        assert!(session.handle(processor.process(ERROR)).is_none());
+        session.run_command(Command::Unmute("3702".to_string()));
+        assert!(session.handle(processor.process(ERROR)).is_some());
+        assert_eq!(session.stats.errors, 3);
+        assert_eq!(session.stats.error_codes["3702"], 3);
+    }
+
+    #[test]
+    fn escalations_get_through_mutes_and_suppression() {
+        let args = Args {
+            error_rules: vec![json5::from_str(
+                r#"{ error_code: 3702, action: ["quiet", "escalate", "suppress"] }"#,
+            )
+            .unwrap()],
+            ..Args::default()
+        };
+        let mut processor = LineProcessor::new(&args);
+        let mut session = session();
+        session.run_command(Command::Mute("3702".to_string()));
+        assert!(matches!(
+            session.handle(processor.process(ERROR)),
+            Some(NotificationType::Escalation)
+        ));
+        assert_eq!(session.stats.rule_errors, 1);
+    }
+
+    #[test]
+    fn counts_lines_per_rule() {
+        let args = Args {
+            error_rules: vec![
+                json5::from_str(r#"{ error_code: 3702, action: "quiet" }"#).unwrap(),
+                json5::from_str(r#"{ error_code: 114, action: "quiet" }"#).unwrap(),
+                json5::from_str(r#"{ message_contains: "wrong", action: "ignore" }"#).unwrap(),
+            ],
+            ..Args::default()
+        };
+        let mut processor = LineProcessor::new(&args);
+        let mut session = session();
+        session.handle(processor.process(ERROR));
+        session.handle(processor.process(ERROR));
+        assert_eq!(session.stats.rule_hits, BTreeMap::from([(0, 2), (2, 2)]));
+    }
+
+    #[test]
+    fn timed_mutes_add_rules_until_unmuted() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut session = session();
+        session.run_command(Command::MuteFor(
+            "3702".to_string(),
+            chrono::Duration::minutes(30),
+        ));
+        session.update_processor(&mut processor);
+        let line = processor.process(ERROR);
+        assert_eq!(line.actions.rules, [0]);
+        assert!(session.handle(line).is_none());
+
+        session.run_command(Command::Unmute("3702".to_string()));
+        session.update_processor(&mut processor);
+        assert!(processor.process(ERROR).actions.rules.is_empty());
+    }
+
+    #[test]
+    fn overlong_timed_mutes_are_refused() {
+        let mut session = session();
+        let duration = crate::timestamp::parse_duration("99999999d").unwrap();
+        session.run_command(Command::MuteFor("3702".to_string(), duration));
+        assert!(session.new_rules.is_empty());
+    }
+
+    #[test]
+    fn repeats_collapsed_by_dedup_dont_notify() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let printer = Printer::new(
+            LineStyles::default(),
+            Highlighter::default(),
+            true,
+            false,
+            LineFilter::default(),
+            Context::default(),
+            Some(Deduper::new(None)),
+        );
+        let mut session = Session::new(printer, DEFAULT_HISTORY_SIZE);
+        let notifications = (0..200)
+            .filter_map(|_| session.handle(processor.process(ERROR)))
+            .count();
+        assert_eq!(notifications, 1);
+    }
+
+    #[test]
+    fn pause_buffers_lines_until_resume() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut session = session();
+        session.run_command(Command::Pause);
+        session.handle(processor.process(ERROR));
+        assert_eq!(
+            session.pending_counts(),
+            Counts {
+                lines: 1,
+                operations: 0,
+                errors: 1,
+                warnings: 0
+            }
+        );
+        session.run_command(Command::Resume { summary: true });
+        assert_eq!(session.pending().count(), 0);
+        assert_eq!(session.history.len(), 1);
+    }
+
+    #[test]
+    fn history_keeps_the_latest_lines() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let printer = Printer::new(
+            LineStyles::default(),
+            Highlighter::default(),
+            true,
+            false,
+            LineFilter::default(),
+            Context::default(),
+            None,
+        );
+        let mut session = Session::new(printer, 2);
+        session.run_command(Command::Mark {
+            label: None,
+            summary: true,
+        });
+        session.run_command(Command::Pause);
+        for _ in 0..3 {
+            session.handle(processor.process(ERROR));
+        }
+        assert_eq!(session.history.len(), 2);
+        assert_eq!(session.pending_counts().errors, 3);
+        assert_eq!(session.pending().count(), 2);
+        // markers count the lines that are no longer kept too
+        assert!(session
+            .marker_text(None, true)
+            .ends_with("since previous marker: 3 errors, 0 warnings"));
+        session.run_command(Command::Resume { summary: false });
+        assert_eq!(session.pending_counts(), Counts::default());
+    }
+
+    #[test]
+    fn markers_count_since_previous_marker() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut session = session();
+        session.handle(processor.process(ERROR));
+        let text = session.marker_text(Some("before paste"), true);
+        assert!(text.contains(" before paste "));
+        assert!(text.ends_with("since start: 1 error, 0 warnings"));
+        assert!(session
+            .marker_text(Some("before paste"), false)
+            .ends_with(" before paste ━━━━"));
+
+        session.run_command(Command::Mark {
+            label: None,
+            summary: false,
+        });
+        assert!(session
+            .marker_text(None, true)
+            .ends_with("since previous marker: 0 errors, 0 warnings"));
+    }
+
+    #[test]
+    fn filtered_lines_dont_notify() {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut session = session();
+        session.run_command(Command::Warnings);
+        assert!(session.handle(processor.process(ERROR)).is_none());
+        assert!(!session.stats.error_codes.is_empty());
+    }
+}
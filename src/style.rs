@@ -0,0 +1,496 @@
+use crate::color_support::ColorSupport;
+use crate::color_type::ColorType;
+use crate::config_file::{ConfigColor, ConfigColorFields, ConfigLineStyles};
+use crate::error_rule::RuleActions;
+use crate::highlight::Highlighter;
+use crate::{ImportLogLine, LineType};
+use colored::Color;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+const DEFAULT_SEPARATOR: &str = "-----------------------------------------------------------------";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Attribute {
+    Bold,
+    Dimmed,
+    Italic,
+    Underline,
+    Reversed,
+}
+
+/// the order colored writes attributes in
+const ATTRIBUTE_ORDER: [Attribute; 5] = [
+    Attribute::Bold,
+    Attribute::Dimmed,
+    Attribute::Underline,
+    Attribute::Reversed,
+    Attribute::Italic,
+];
+
+impl Attribute {
+    fn code(&self) -> &'static str {
+        match self {
+            Attribute::Bold => "1",
+            Attribute::Dimmed => "2",
+            Attribute::Italic => "3",
+            Attribute::Underline => "4",
+            Attribute::Reversed => "7",
+        }
+    }
+}
+
+impl FromStr for Attribute {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bold" => Ok(Attribute::Bold),
+            "dim" | "dimmed" => Ok(Attribute::Dimmed),
+            "italic" => Ok(Attribute::Italic),
+            "underline" | "underlined" => Ok(Attribute::Underline),
+            "reverse" | "reversed" => Ok(Attribute::Reversed),
+            _ => Err(format!(
+                "unknown text attribute: \"{s}\", expected one of bold, dim, italic, underline, reverse"
+            )),
+        }
+    }
+}
+
+/// A resolved foreground, background and set of text attributes. `None` colors leave the terminal default in place.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Style {
+    pub(crate) foreground: Option<ColorType>,
+    pub(crate) background: Option<ColorType>,
+    pub(crate) attributes: Vec<Attribute>,
+}
+
+impl Style {
+    fn fg(color: &str) -> Self {
+        Style {
+            foreground: ColorType::from_str(color).ok(),
+            ..Style::default()
+        }
+    }
+
+    fn on(mut self, color: &str) -> Self {
+        self.background = ColorType::from_str(color).ok();
+        self
+    }
+
+    fn with(mut self, attribute: Attribute) -> Self {
+        if !self.attributes.contains(&attribute) {
+            self.attributes.push(attribute);
+        }
+        self
+    }
+
+    /// layer a config entry on top of this style. Empty config fields keep the current value, and "none" removes the color. `path` is only used to point at the offending config key in errors.
+    pub(crate) fn merge(&self, config: &ConfigColor, path: &str) -> Result<Style, String> {
+        let parse_color = |color: &str, key: &str| match color.trim() {
+            "none" => Ok(None),
+            color => ColorType::from_str(color)
+                .map(Some)
+                .map_err(|e| format!("{path}.{key}: {e}")),
+        };
+        let mut style = self.clone();
+        if !config.foreground.is_empty() {
+            style.foreground = parse_color(&config.foreground, "foreground")?;
+        }
+        if !config.background.is_empty() {
+            style.background = parse_color(&config.background, "background")?;
+        }
+        if let Some(attributes) = &config.attributes {
+            style.attributes = attributes
+                .iter()
+                .map(|a| Attribute::from_str(a).map_err(|e| format!("{path}.attributes: {e}")))
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(style)
+    }
+
+    /// layer `top` on top of this style. Its colors win where set, and attributes are combined.
+    pub(crate) fn layer(&self, top: &Style) -> Style {
+        let mut style = self.clone();
+        if top.foreground.is_some() {
+            style.foreground = top.foreground.clone();
+        }
+        if top.background.is_some() {
+            style.background = top.background.clone();
+        }
+        for attribute in &top.attributes {
+            style = style.with(*attribute);
+        }
+        style
+    }
+
+    pub(crate) fn downgrade(&self, support: ColorSupport) -> Style {
+        Style {
+            foreground: self.foreground.as_ref().map(|c| c.downgrade(support)),
+            background: self.background.as_ref().map(|c| c.downgrade(support)),
+            attributes: self.attributes.clone(),
+        }
+    }
+
+    /// used for context lines
+    pub(crate) fn dimmed(&self) -> Style {
+        self.clone().with(Attribute::Dimmed)
+    }
+
+    /// bold, for marker lines
+    pub(crate) fn bold(&self) -> Style {
+        self.clone().with(Attribute::Bold)
+    }
+
+    /// Paint `text` with escape codes in the same order colored uses. Rgb colors are written as they are,
+    /// because `downgrade` has already matched them to the terminal, while colored would downgrade them again unless COLORTERM says truecolor.
+    pub(crate) fn paint(&self, text: &str) -> String {
+        let codes = self.codes();
+        if codes.is_empty() || !colored::control::SHOULD_COLORIZE.should_colorize() {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{text}\x1b[0m", codes.join(";"))
+    }
+
+    /// the SGR codes for the attributes, background and foreground
+    fn codes(&self) -> Vec<Cow<'static, str>> {
+        let mut codes: Vec<Cow<str>> = ATTRIBUTE_ORDER
+            .iter()
+            .filter(|a| self.attributes.contains(a))
+            .map(|a| Cow::Borrowed(a.code()))
+            .collect();
+        if let Some(color) = &self.background {
+            codes.push(match color {
+                ColorType::Rgb(r, g, b) => format!("48;2;{r};{g};{b}").into(),
+                ColorType::Ansi(ansi) => Color::from(ansi.as_str()).to_bg_str(),
+                ColorType::Ansi256(index) => Color::AnsiColor(*index).to_bg_str(),
+            });
+        }
+        if let Some(color) = &self.foreground {
+            codes.push(match color {
+                ColorType::Rgb(r, g, b) => format!("38;2;{r};{g};{b}").into(),
+                ColorType::Ansi(ansi) => Color::from(ansi.as_str()).to_fg_str(),
+                ColorType::Ansi256(index) => Color::AnsiColor(*index).to_fg_str(),
+            });
+        }
+        codes
+    }
+}
+
+/// One style per column of an import log line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct LineStyle {
+    pub(crate) timestamp: Style,
+    pub(crate) filename: Style,
+    pub(crate) error: Style,
+    pub(crate) message: Style,
+}
+
+impl LineStyle {
+    fn merge(&self, config: &ConfigColorFields, path: &str) -> Result<LineStyle, String> {
+        Ok(LineStyle {
+            timestamp: self
+                .timestamp
+                .merge(&config.timestamp, &format!("{path}.timestamp"))?,
+            filename: self
+                .filename
+                .merge(&config.filename, &format!("{path}.filename"))?,
+            error: self.error.merge(&config.error, &format!("{path}.error"))?,
+            message: self
+                .message
+                .merge(&config.message, &format!("{path}.message"))?,
+        })
+    }
+
+    fn map(&self, f: impl Fn(&Style) -> Style) -> LineStyle {
+        LineStyle {
+            timestamp: f(&self.timestamp),
+            filename: f(&self.filename),
+            error: f(&self.error),
+            message: f(&self.message),
+        }
+    }
+
+    pub(crate) fn dimmed(&self) -> LineStyle {
+        self.map(|s| s.dimmed())
+    }
+
+    /// layer an error rule's highlight over the timestamp, location and code columns
+    pub(crate) fn highlighted(&self, highlight: &Style) -> LineStyle {
+        LineStyle {
+            timestamp: self.timestamp.layer(highlight),
+            filename: self.filename.layer(highlight),
+            error: self.error.layer(highlight),
+            message: self.message.clone(),
+        }
+    }
+
+    fn downgrade(&self, support: ColorSupport) -> LineStyle {
+        self.map(|s| s.downgrade(support))
+    }
+
+    pub(crate) fn paint(&self, line: &ImportLogLine, highlighter: &Highlighter) -> [String; 4] {
+        [
+            highlighter.paint_search(&line.timestamp, &self.timestamp),
+            highlighter.paint_search(&line.filename, &self.filename),
+            highlighter.paint_search(&line.code, &self.error),
+            highlighter.paint(&line.message, &self.message),
+        ]
+    }
+}
+
+/// Resolved styles for every kind of line fmrl prints
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LineStyles {
+    pub(crate) success: LineStyle,
+    pub(crate) warning: LineStyle,
+    pub(crate) error: LineStyle,
+    pub(crate) quiet_error: LineStyle,
+    pub(crate) header: LineStyle,
+    pub(crate) other: Style,
+    pub(crate) separator: Style,
+    pub(crate) separator_text: String,
+}
+
+impl Default for LineStyles {
+    fn default() -> Self {
+        let success = LineStyle {
+            timestamp: Style::fg("cyan"),
+            filename: Style::fg("green"),
+            error: Style::fg("bright magenta"),
+            message: Style::fg("bright blue"),
+        };
+        let highlight = |fg: &str, bg: &str| LineStyle {
+            timestamp: Style::fg(fg).on(bg),
+            filename: Style::fg(fg).on(bg),
+            error: Style::fg(fg).on(bg),
+            message: Style::default(),
+        };
+        let error = highlight("bright white", "red");
+        LineStyles {
+            header: success.map(|s| s.clone().with(Attribute::Underline)),
+            success,
+            warning: highlight("black", "yellow"),
+            quiet_error: error.clone(),
+            error,
+            other: Style::default(),
+            separator: Style::default(),
+            separator_text: DEFAULT_SEPARATOR.to_string(),
+        }
+    }
+}
+
+impl LineStyles {
+    /// `colors` sets the base column colors, used by success and header lines. `line_styles` is layered on top per line type.
+    /// Returns an error naming the config key of the first invalid color or attribute.
+    pub(crate) fn from_config(
+        colors: &ConfigColorFields,
+        line_styles: &ConfigLineStyles,
+    ) -> Result<Self, String> {
+        let default = LineStyles::default();
+        let success = default.success.merge(colors, "colors")?;
+        // the header keeps its underline unless the user overrides the attributes
+        let header = success.map(|s| s.clone().with(Attribute::Underline));
+        let error = default
+            .error
+            .merge(&line_styles.error, "line_styles.error")?;
+        let separator_text = if line_styles.separator.text.is_empty() {
+            default.separator_text
+        } else {
+            line_styles.separator.text.clone()
+        };
+        Ok(LineStyles {
+            success: success.merge(&line_styles.success, "line_styles.success")?,
+            warning: default
+                .warning
+                .merge(&line_styles.warning, "line_styles.warning")?,
+            // quieted errors look like errors unless configured otherwise
+            quiet_error: error.merge(&line_styles.quiet_error, "line_styles.quiet_error")?,
+            error,
+            header: header.merge(&line_styles.header, "line_styles.header")?,
+            other: default
+                .other
+                .merge(&line_styles.other, "line_styles.other")?,
+            separator: default
+                .separator
+                .merge(&line_styles.separator.color, "line_styles.separator")?,
+            separator_text,
+        })
+    }
+
+    /// the style for an import log line, taking the error rule action into account. `None` for lines that aren't import log lines.
+    pub(crate) fn for_line(&self, line: &LineType, actions: &RuleActions) -> Option<&LineStyle> {
+        match line {
+            LineType::Success(_) => Some(&self.success),
+            // ignored errors and warnings look like any other line
+            LineType::Error(_) | LineType::Warning(_) if actions.ignore => Some(&self.success),
+            LineType::Error(_) if actions.quiet => Some(&self.quiet_error),
+            LineType::Error(_) => Some(&self.error),
+            LineType::Warning(_) => Some(&self.warning),
+            LineType::Header(_) => Some(&self.header),
+            LineType::Other(_) => None,
+        }
+    }
+
+    /// map every color to the nearest one the terminal supports
+    pub(crate) fn downgrade(&self, support: ColorSupport) -> Self {
+        LineStyles {
+            success: self.success.downgrade(support),
+            warning: self.warning.downgrade(support),
+            error: self.error.downgrade(support),
+            quiet_error: self.quiet_error.downgrade(support),
+            header: self.header.downgrade(support),
+            other: self.other.downgrade(support),
+            separator: self.separator.downgrade(support),
+            separator_text: self.separator_text.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_from_str() {
+        assert_eq!(Attribute::from_str("bold"), Ok(Attribute::Bold));
+        assert_eq!(Attribute::from_str(" Underline "), Ok(Attribute::Underline));
+        assert_eq!(Attribute::from_str("dim"), Ok(Attribute::Dimmed));
+        assert_eq!(Attribute::from_str("reverse"), Ok(Attribute::Reversed));
+        assert!(Attribute::from_str("sparkly").is_err());
+    }
+
+    #[test]
+    fn invalid_config_reports_the_offending_key() {
+        let line_styles: ConfigLineStyles =
+            json5::from_str(r##"{ warning: { filename: { background: "#12345" } } }"##).unwrap();
+        let err = LineStyles::from_config(&ConfigColorFields::default(), &line_styles).unwrap_err();
+        assert!(err.starts_with("line_styles.warning.filename.background:"));
+
+        let colors: ConfigColorFields =
+            json5::from_str(r#"{ message: { attributes: ["bold", "blinky"] } }"#).unwrap();
+        let err = LineStyles::from_config(&colors, &ConfigLineStyles::default()).unwrap_err();
+        assert!(err.starts_with("colors.message.attributes: unknown text attribute"));
+    }
+
+    #[test]
+    fn merge_keeps_unset_fields() {
+        let style = Style::fg("red").on("blue").with(Attribute::Bold);
+        let config = ConfigColor {
+            foreground: "green".to_string(),
+            ..ConfigColor::default()
+        };
+        let merged = style.merge(&config, "test").unwrap();
+        assert_eq!(
+            merged.foreground,
+            Some(ColorType::Ansi("green".to_string()))
+        );
+        assert_eq!(merged.background, Some(ColorType::Ansi("blue".to_string())));
+        assert_eq!(merged.attributes, vec![Attribute::Bold]);
+
+        // "none" removes the color
+        let config = ConfigColor {
+            background: "none".to_string(),
+            ..ConfigColor::default()
+        };
+        assert_eq!(style.merge(&config, "test").unwrap().background, None);
+
+        // an explicit empty list clears the attributes
+        let config = ConfigColor {
+            attributes: Some(vec![]),
+            ..ConfigColor::default()
+        };
+        assert_eq!(style.merge(&config, "test").unwrap().attributes, vec![]);
+    }
+
+    #[test]
+    fn layer_combines_attributes() {
+        let base = Style::fg("red").on("blue").with(Attribute::Bold);
+        let top = Style::fg("green").with(Attribute::Underline);
+        let layered = base.layer(&top);
+        assert_eq!(
+            layered.foreground,
+            Some(ColorType::Ansi("green".to_string()))
+        );
+        assert_eq!(
+            layered.background,
+            Some(ColorType::Ansi("blue".to_string()))
+        );
+        assert_eq!(
+            layered.attributes,
+            vec![Attribute::Bold, Attribute::Underline]
+        );
+    }
+
+    #[test]
+    fn codes_keep_truecolor() {
+        let style = Style {
+            foreground: Some(ColorType::Rgb(255, 136, 0)),
+            background: Some(ColorType::Ansi("blue".to_string())),
+            attributes: vec![Attribute::Italic, Attribute::Bold],
+        };
+        // whatever COLORTERM says
+        assert_eq!(style.codes(), ["1", "3", "44", "38;2;255;136;0"]);
+        assert_eq!(Style::fg("ansi(196)").codes(), ["38;5;196"]);
+        assert!(Style::default().codes().is_empty());
+    }
+
+    #[test]
+    fn default_config_matches_default_styles() {
+        let styles =
+            LineStyles::from_config(&ConfigColorFields::default(), &ConfigLineStyles::default())
+                .unwrap();
+        assert_eq!(styles, LineStyles::default());
+    }
+
+    #[test]
+    fn colors_apply_to_success_and_header_lines() {
+        let colors = ConfigColorFields {
+            timestamp: ConfigColor {
+                foreground: "#ff0000".to_string(),
+                ..ConfigColor::default()
+            },
+            ..ConfigColorFields::default()
+        };
+        let styles = LineStyles::from_config(&colors, &ConfigLineStyles::default()).unwrap();
+        assert_eq!(
+            styles.success.timestamp.foreground,
+            Some(ColorType::Rgb(255, 0, 0))
+        );
+        assert_eq!(
+            styles.header.timestamp.foreground,
+            Some(ColorType::Rgb(255, 0, 0))
+        );
+        assert_eq!(
+            styles.header.timestamp.attributes,
+            vec![Attribute::Underline]
+        );
+        assert_eq!(styles.error, LineStyles::default().error);
+    }
+
+    #[test]
+    fn quiet_error_inherits_from_error() {
+        let line_styles: ConfigLineStyles = json5::from_str(
+            r#"{
+                error: { message: { foreground: "red" } },
+                quiet_error: { timestamp: { background: "magenta" } },
+                separator: { text: "=====", foreground: "yellow", attributes: ["bold"] },
+            }"#,
+        )
+        .unwrap();
+        let styles = LineStyles::from_config(&ConfigColorFields::default(), &line_styles).unwrap();
+        assert_eq!(
+            styles.quiet_error.message.foreground,
+            Some(ColorType::Ansi("red".to_string()))
+        );
+        assert_eq!(
+            styles.quiet_error.timestamp.background,
+            Some(ColorType::Ansi("magenta".to_string()))
+        );
+        assert_eq!(
+            styles.error.timestamp.background,
+            Some(ColorType::Ansi("red".to_string()))
+        );
+        assert_eq!(styles.separator_text, "=====");
+        assert_eq!(styles.separator.attributes, vec![Attribute::Bold]);
+    }
+}
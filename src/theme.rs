@@ -0,0 +1,96 @@
+use crate::config_file::{ConfigColorFields, ConfigLineStyles};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::Read;
+
+// built-in themes use the same format as theme files, so they double as examples
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("themes/dark.json5")),
+    ("light", include_str!("themes/light.json5")),
+    ("high-contrast", include_str!("themes/high-contrast.json5")),
+    ("solarized", include_str!("themes/solarized.json5")),
+    ("monochrome", include_str!("themes/monochrome.json5")),
+];
+
+/// A theme file holds the same `colors` and `line_styles` keys as the config file
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Theme {
+    pub(crate) colors: ConfigColorFields,
+    pub(crate) line_styles: ConfigLineStyles,
+}
+
+impl Theme {
+    /// layer the config's own `colors` and `line_styles` on top of the theme
+    pub(crate) fn layer(&mut self, colors: &ConfigColorFields, line_styles: &ConfigLineStyles) {
+        self.colors.layer(colors);
+        self.line_styles.layer(line_styles);
+    }
+}
+
+pub(crate) fn builtin_theme_names() -> Vec<&'static str> {
+    BUILTIN_THEMES.iter().map(|(name, _)| *name).collect()
+}
+
+pub(crate) fn is_builtin_theme(name: &str) -> bool {
+    BUILTIN_THEMES.iter().any(|(n, _)| *n == name)
+}
+
+/// `name_or_path` is either the name of a built-in theme or a path to a theme file
+pub(crate) fn load_theme(name_or_path: &str) -> Result<Theme, String> {
+    if let Some((name, json)) = BUILTIN_THEMES.iter().find(|(n, _)| *n == name_or_path) {
+        return json5::from_str(json).map_err(|e| format!("couldn't parse theme {name}: {e}"));
+    }
+
+    let path = std::path::Path::new(name_or_path);
+    if !path.exists() {
+        return Err(format!(
+            "theme not found: {}. Use one of {} or a path to a theme file",
+            name_or_path,
+            builtin_theme_names().join(", ")
+        ));
+    }
+    let mut buf = String::new();
+    File::open(path)
+        .map_err(|e| format!("couldn't open theme file: {}", e))?
+        .read_to_string(&mut buf)
+        .map_err(|e| format!("couldn't read theme file: {}", e))?;
+    json5::from_str(&buf).map_err(|e| format!("couldn't parse theme file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::style::LineStyles;
+
+    #[test]
+    fn builtin_themes_are_valid() {
+        for name in builtin_theme_names() {
+            let theme = load_theme(name).unwrap();
+            LineStyles::from_config(&theme.colors, &theme.line_styles).unwrap();
+        }
+    }
+
+    #[test]
+    fn dark_theme_matches_default_styles() {
+        let theme = load_theme("dark").unwrap();
+        let styles = LineStyles::from_config(&theme.colors, &theme.line_styles).unwrap();
+        assert_eq!(styles, LineStyles::default());
+    }
+
+    #[test]
+    fn unknown_theme_is_err() {
+        let err = load_theme("not-a-theme").unwrap_err();
+        assert!(err.contains("dark, light"));
+    }
+
+    #[test]
+    fn config_colors_layer_on_top_of_theme() {
+        let mut theme = load_theme("light").unwrap();
+        let colors: ConfigColorFields =
+            json5::from_str(r#"{ timestamp: { background: "white" } }"#).unwrap();
+        theme.layer(&colors, &ConfigLineStyles::default());
+        assert_eq!(theme.colors.timestamp.foreground, "blue");
+        assert_eq!(theme.colors.timestamp.background, "white");
+    }
+}
@@ -0,0 +1,144 @@
+use crate::error_rule::{ErrorRule, RuleActions, Threshold};
+use chrono::{DateTime, Duration, FixedOffset};
+use std::collections::{HashMap, VecDeque};
+
+/// A line counted towards a rule's threshold
+struct Counted {
+    timestamp: Option<DateTime<FixedOffset>>,
+    operation: Option<usize>,
+}
+
+/// Counts the lines matching rules with a `threshold`, and applies those rules once enough lines match.
+/// Rules are counted by their position in the list of rules, so keep one per list.
+#[derive(Default)]
+pub(crate) struct Thresholds {
+    counted: HashMap<usize, VecDeque<Counted>>,
+}
+
+impl Thresholds {
+    /// Count the line for each rule in `actions.counting`, and add the actions of the rules whose threshold it reaches
+    pub(crate) fn apply(
+        &mut self,
+        rules: &[ErrorRule],
+        actions: &mut RuleActions,
+        timestamp: Option<DateTime<FixedOffset>>,
+        operation: Option<usize>,
+    ) {
+        for i in std::mem::take(&mut actions.counting) {
+            let rule = &rules[i];
+            let Some(threshold) = rule.threshold() else {
+                continue;
+            };
+            let counted = self.counted.entry(i).or_default();
+            counted.retain(|c| still_counts(threshold, c, timestamp, operation));
+            counted.push_back(Counted {
+                timestamp,
+                operation,
+            });
+            if counted.len() >= threshold.count {
+                counted.clear();
+                actions.apply_rule(i, rule);
+            }
+        }
+    }
+}
+
+/// whether an earlier line still counts towards the threshold when a line at `timestamp` in `operation` matches
+fn still_counts(
+    threshold: &Threshold,
+    counted: &Counted,
+    timestamp: Option<DateTime<FixedOffset>>,
+    operation: Option<usize>,
+) -> bool {
+    if threshold.per_operation && counted.operation != operation {
+        return false;
+    }
+    match threshold.within {
+        Some(secs) => match (counted.timestamp, timestamp) {
+            (Some(then), Some(now)) => now - then <= Duration::milliseconds((secs * 1000.0) as i64),
+            // without timestamps there's no telling how far apart they are
+            _ => false,
+        },
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error_rule::deserialize_error_rules;
+    use crate::line_processor::LineProcessor;
+    use crate::notifications::NotificationType;
+    use crate::Args;
+
+    const START: &str =
+        "2023-05-03 08:58:37.211 +0200\tmy.fmp12\t0\tImport of script steps from clipboard started";
+
+    fn line(time: &str, code: &str) -> String {
+        format!("2023-05-03 {time} +0200\tMy Script\t{code}\tsomething went wrong")
+    }
+
+    fn processor(rules: &str) -> LineProcessor {
+        LineProcessor::new(&Args {
+            error_rules: deserialize_error_rules(
+                &mut json5::Deserializer::from_str(rules).unwrap(),
+            )
+            .unwrap(),
+            ..Args::default()
+        })
+    }
+
+    #[test]
+    fn counts_within_a_window() {
+        let mut processor = processor(
+            r#"[
+                { error_code: 3702, action: "quiet" },
+                { error_code: 3702, threshold: { count: 3, within: 60 }, action: "escalate" },
+            ]"#,
+        );
+        let escalated = [
+            "08:58:00.000",
+            "08:58:10.000",
+            // too late for the first, so only two count
+            "08:59:05.000",
+            "08:59:06.000",
+            // counting starts over after the rule applies
+            "08:59:07.000",
+        ]
+        .map(|time| {
+            let line = processor.process(&line(time, "3702"));
+            matches!(line.notification, Some(NotificationType::Escalation))
+        });
+        assert_eq!(escalated, [false, false, false, true, false]);
+        // other codes don't count
+        assert!(processor
+            .process(&line("08:59:08.000", "114"))
+            .actions
+            .rules
+            .is_empty());
+    }
+
+    #[test]
+    fn counts_per_operation() {
+        let mut processor = processor(
+            r#"[
+                { line_type: "error", threshold: { count: 2, per_operation: true }, action: { tag: "again" } },
+            ]"#,
+        );
+        processor.process(START);
+        assert!(processor
+            .process(&line("08:58:38.000", "114"))
+            .actions
+            .tags
+            .is_empty());
+        processor.process(START);
+        assert!(processor
+            .process(&line("08:58:39.000", "3702"))
+            .actions
+            .tags
+            .is_empty());
+        let second = processor.process(&line("08:58:40.000", "114"));
+        assert_eq!(second.actions.tag_text(), " [again]");
+        assert_eq!(second.actions.rules, [0]);
+        assert!(second.actions.counting.is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+// FileMaker writes timestamps like `2023-05-03 09:00:53.305 +0200`
+const IMPORT_LOG_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f %z";
+
+#[derive(ValueEnum, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum TimestampFormat {
+    /// print the timestamp exactly as it appears in the log
+    #[default]
+    Full,
+    /// time of day only, e.g. 09:00:53.305
+    Time,
+    /// full timestamp converted to the local time zone
+    Local,
+    /// time of day only, converted to the local time zone
+    LocalTime,
+    /// time elapsed since the line was logged, e.g. 12s ago
+    Relative,
+    /// time elapsed since the previous line
+    Delta,
+    /// time elapsed since the start of the current import operation
+    SinceOperation,
+}
+
+pub(crate) fn parse_timestamp(timestamp: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(timestamp.trim(), IMPORT_LOG_TIMESTAMP_FORMAT).ok()
+}
+
+/// Parses durations like `90s`, `30m`, `2h`, `1d` or `1h30m`
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
+    let err = || format!("expected a duration like 30m, 2h or 1h30m, got \"{s}\"");
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: i64 = number.parse().map_err(|_| err())?;
+        number.clear();
+        let part = match c {
+            's' => Duration::try_seconds(n),
+            'm' => Duration::try_minutes(n),
+            'h' => Duration::try_hours(n),
+            'd' => Duration::try_days(n),
+            _ => return Err(err()),
+        };
+        total = part
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(|| format!("duration \"{s}\" is too long"))?;
+    }
+    if !number.is_empty() || total.is_zero() {
+        return Err(err());
+    }
+    Ok(total)
+}
+
+/// Parses a local date and time like `2026-10-18 18:00`, a date (meaning the start of that day), or a timestamp with an offset like the log's
+pub(crate) fn parse_local_datetime(s: &str) -> Option<DateTime<Local>> {
+    let s = s.trim();
+    if let Some(dt) = parse_timestamp(s).or_else(|| DateTime::parse_from_rfc3339(s).ok()) {
+        return Some(dt.with_timezone(&Local));
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+    naive.and_local_timezone(Local).earliest()
+}
+
+/// Formats durations compactly, e.g. 0.003s, 12.5s, 2m05s, 1h02m, 3d04h
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.num_milliseconds().max(0);
+    let secs = millis / 1000;
+    if secs < 60 {
+        format!("{}.{:03}s", secs, millis % 1000)
+    } else if secs < 60 * 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else if secs < 60 * 60 * 24 {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    } else {
+        format!("{}d{:02}h", secs / 86400, (secs % 86400) / 3600)
+    }
+}
+
+fn format_relative(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 60 * 60 * 24 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
+
+/// Rewrites timestamps for display. Delta formats are stateful, so use one formatter per log and feed it every line in order.
+#[derive(Default)]
+pub(crate) struct TimestampFormatter {
+    format: TimestampFormat,
+    previous: Option<DateTime<FixedOffset>>,
+    operation_start: Option<DateTime<FixedOffset>>,
+}
+
+impl TimestampFormatter {
+    pub(crate) fn new(format: TimestampFormat) -> Self {
+        TimestampFormatter {
+            format,
+            ..TimestampFormatter::default()
+        }
+    }
+
+    /// Returns the display version of `timestamp`. Unparseable timestamps are returned unchanged.
+    pub(crate) fn format(&mut self, timestamp: &str, is_operation_start: bool) -> String {
+        let Some(datetime) = parse_timestamp(timestamp) else {
+            return timestamp.to_string();
+        };
+        let previous = self.previous.replace(datetime);
+        if is_operation_start || self.operation_start.is_none() {
+            self.operation_start = Some(datetime);
+        }
+
+        match self.format {
+            TimestampFormat::Full => timestamp.to_string(),
+            TimestampFormat::Time => datetime.format("%H:%M:%S%.3f").to_string(),
+            TimestampFormat::Local => datetime
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S%.3f %z")
+                .to_string(),
+            TimestampFormat::LocalTime => datetime
+                .with_timezone(&Local)
+                .format("%H:%M:%S%.3f")
+                .to_string(),
+            TimestampFormat::Relative => {
+                format_relative(Local::now().fixed_offset().signed_duration_since(datetime))
+            }
+            TimestampFormat::Delta => {
+                let since = previous.unwrap_or(datetime);
+                format!("+{}", format_duration(datetime - since))
+            }
+            TimestampFormat::SinceOperation => {
+                let since = self.operation_start.unwrap_or(datetime);
+                format!("+{}", format_duration(datetime - since))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TS: &str = "2023-05-03 09:00:53.305 +0200";
+
+    #[test]
+    fn test_parse_timestamp() {
+        let datetime = parse_timestamp(TS).unwrap();
+        assert_eq!(datetime.timestamp_millis(), 1683097253305);
+        assert!(parse_timestamp("foo").is_none());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::hours(24)));
+        assert!(parse_duration("9999999999999999d").is_err());
+        assert!(parse_duration("99999999999999999d").is_err());
+        for s in ["", "30", "m", "0m", "5 minutes", "-5m"] {
+            assert!(parse_duration(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn test_parse_local_datetime() {
+        let at = |s| parse_local_datetime(s).map(|dt| dt.naive_local().to_string());
+        assert_eq!(
+            at("2026-10-18 18:00"),
+            Some("2026-10-18 18:00:00".to_string())
+        );
+        assert_eq!(
+            at("2026-10-18T18:00:30"),
+            Some("2026-10-18 18:00:30".to_string())
+        );
+        assert_eq!(at("2026-10-18"), Some("2026-10-18 00:00:00".to_string()));
+        assert_eq!(
+            parse_local_datetime(TS).map(|dt| dt.timestamp_millis()),
+            Some(1683097253305)
+        );
+        assert!(parse_local_datetime("8h").is_none());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::milliseconds(3)), "0.003s");
+        assert_eq!(format_duration(Duration::milliseconds(12500)), "12.500s");
+        assert_eq!(format_duration(Duration::seconds(125)), "2m05s");
+        assert_eq!(format_duration(Duration::seconds(3720)), "1h02m");
+        assert_eq!(format_duration(Duration::hours(76)), "3d04h");
+        assert_eq!(format_duration(Duration::seconds(-5)), "0.000s");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(Duration::seconds(12)), "12s ago");
+        assert_eq!(format_relative(Duration::seconds(125)), "2m ago");
+        assert_eq!(format_relative(Duration::hours(5)), "5h ago");
+        assert_eq!(format_relative(Duration::hours(50)), "2d ago");
+    }
+
+    #[test]
+    fn full_and_time_formats() {
+        let mut formatter = TimestampFormatter::new(TimestampFormat::Full);
+        assert_eq!(formatter.format(TS, false), TS);
+        let mut formatter = TimestampFormatter::new(TimestampFormat::Time);
+        assert_eq!(formatter.format(TS, false), "09:00:53.305");
+        // not a timestamp, leave it alone
+        assert_eq!(formatter.format("Timestamp", false), "Timestamp");
+    }
+
+    #[test]
+    fn delta_formats() {
+        let lines = [
+            ("2023-05-03 09:00:53.305 +0200", true),
+            ("2023-05-03 09:00:53.310 +0200", false),
+            ("2023-05-03 09:00:55.310 +0200", false),
+            ("2023-05-03 09:01:10.000 +0200", true),
+            ("2023-05-03 09:01:10.250 +0200", false),
+        ];
+
+        let mut formatter = TimestampFormatter::new(TimestampFormat::Delta);
+        let res = lines.map(|(ts, start)| formatter.format(ts, start));
+        assert_eq!(
+            res,
+            ["+0.000s", "+0.005s", "+2.000s", "+14.690s", "+0.250s"]
+        );
+
+        let mut formatter = TimestampFormatter::new(TimestampFormat::SinceOperation);
+        let res = lines.map(|(ts, start)| formatter.format(ts, start));
+        assert_eq!(res, ["+0.000s", "+0.005s", "+2.005s", "+0.000s", "+0.250s"]);
+    }
+}
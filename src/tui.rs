@@ -0,0 +1,824 @@
+use crate::color_type::ColorType;
+use crate::filter::LineFilter;
+use crate::highlight::{search_regex, Highlighter};
+use crate::line_processor::{LineProcessor, ProcessedLine};
+use crate::log_file::{self, LogFile};
+use crate::notifications::NotificationType;
+use crate::style::{self, LineStyles};
+use crate::LineType;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc::{Sender, TryRecvError};
+use std::time::Duration;
+
+const HELP: &str = "q quit  ↑↓/jk move  / search  n/N next/prev  e errors  w warnings  a all  space collapse  c/x collapse/expand all  d details";
+
+pub(crate) struct TuiOptions<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) log_file: LogFile,
+    pub(crate) processor: LineProcessor,
+    pub(crate) styles: LineStyles,
+    pub(crate) highlighter: Highlighter,
+    pub(crate) filter: LineFilter,
+    pub(crate) watch: bool,
+    pub(crate) notif_tx: Sender<NotificationType>,
+    pub(crate) send_notif: bool,
+    /// false when colors are off, in which case only text attributes are drawn
+    pub(crate) color: bool,
+}
+
+struct Entry {
+    line: ProcessedLine,
+    /// index into `App::operations`
+    operation: Option<usize>,
+}
+
+/// An import operation: the lines from one operation start up to the next
+struct Operation {
+    first: usize,
+    len: usize,
+    errors: usize,
+    warnings: usize,
+}
+
+/// A row on screen. Collapsed operations take up a single row.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Row {
+    entry: usize,
+    collapsed: bool,
+}
+
+struct Search {
+    query: String,
+    regex: Option<Regex>,
+    /// true while the query is being typed
+    editing: bool,
+}
+
+#[derive(Default)]
+struct App {
+    entries: Vec<Entry>,
+    operations: Vec<Operation>,
+    errors: usize,
+    warnings: usize,
+    filter: LineFilter,
+    collapsed: HashSet<usize>,
+    search: Option<Search>,
+    rows: Vec<Row>,
+    /// index into `rows`
+    selected: usize,
+    /// index of the first row on screen
+    offset: usize,
+    /// keep the last line selected as new lines come in
+    follow: bool,
+    show_detail: bool,
+    quit: bool,
+}
+
+impl App {
+    fn new(filter: LineFilter) -> Self {
+        App {
+            filter,
+            follow: true,
+            ..App::default()
+        }
+    }
+
+    fn push(&mut self, line: ProcessedLine) {
+        let index = self.entries.len();
+        if line.operation_start {
+            self.operations.push(Operation {
+                first: index,
+                len: 0,
+                errors: 0,
+                warnings: 0,
+            });
+        }
+        let operation = self.operations.len().checked_sub(1);
+        if let Some(op) = operation.map(|i| &mut self.operations[i]) {
+            op.len += 1;
+            match &line.line {
+                LineType::Error(_) => op.errors += 1,
+                LineType::Warning(_) => op.warnings += 1,
+                _ => {}
+            }
+        }
+        match &line.line {
+            LineType::Error(_) => self.errors += 1,
+            LineType::Warning(_) => self.warnings += 1,
+            _ => {}
+        }
+        self.entries.push(Entry { line, operation });
+    }
+
+    fn selected_entry(&self) -> Option<usize> {
+        self.rows.get(self.selected).map(|row| row.entry)
+    }
+
+    /// Rebuild the visible rows, keeping the selection on the same entry (or the nearest one after it)
+    fn refresh(&mut self) {
+        let selected_entry = self.selected_entry();
+        self.rows.clear();
+        let mut last_collapsed = None;
+        for (i, entry) in self.entries.iter().enumerate() {
+            if !self.filter.shows(&entry.line) {
+                continue;
+            }
+            match entry.operation {
+                Some(op) if self.collapsed.contains(&op) => {
+                    if last_collapsed != Some(op) {
+                        self.rows.push(Row {
+                            entry: i,
+                            collapsed: true,
+                        });
+                        last_collapsed = Some(op);
+                    }
+                }
+                _ => self.rows.push(Row {
+                    entry: i,
+                    collapsed: false,
+                }),
+            }
+        }
+
+        self.selected = match (self.follow, selected_entry) {
+            (false, Some(entry)) => self
+                .rows
+                .iter()
+                .position(|row| row.entry >= entry || self.row_contains(row, entry))
+                .unwrap_or(self.rows.len().saturating_sub(1)),
+            _ => self.rows.len().saturating_sub(1),
+        };
+    }
+
+    fn row_contains(&self, row: &Row, entry: usize) -> bool {
+        row.collapsed
+            && self.entries[row.entry].operation.is_some()
+            && self.entries[row.entry].operation == self.entries[entry].operation
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let last = self.rows.len() - 1;
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+        self.follow = self.selected == last && delta > 0;
+    }
+
+    fn select_last(&mut self) {
+        self.selected = self.rows.len().saturating_sub(1);
+        self.follow = true;
+    }
+
+    fn select_first(&mut self) {
+        self.selected = 0;
+        self.follow = false;
+    }
+
+    fn set_filter_types(&mut self, errors_only: bool, warnings_only: bool) {
+        self.filter.set_types(errors_only, warnings_only);
+        self.refresh();
+    }
+
+    fn toggle_collapse(&mut self) {
+        let Some(op) = self
+            .selected_entry()
+            .and_then(|entry| self.entries[entry].operation)
+        else {
+            return;
+        };
+        if !self.collapsed.remove(&op) {
+            self.collapsed.insert(op);
+        }
+        self.follow = false;
+        self.refresh();
+    }
+
+    fn collapse_all(&mut self, collapse: bool) {
+        if collapse {
+            self.collapsed = (0..self.operations.len()).collect();
+        } else {
+            self.collapsed.clear();
+        }
+        self.refresh();
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        let Some(regex) = self.search.as_ref().and_then(|s| s.regex.as_ref()) else {
+            return false;
+        };
+        if row.collapsed {
+            let op = &self.operations[self.entries[row.entry].operation.unwrap()];
+            self.entries[op.first..op.first + op.len]
+                .iter()
+                .any(|entry| regex.is_match(&entry.line.line.to_string()))
+        } else {
+            regex.is_match(&self.entries[row.entry].line.line.to_string())
+        }
+    }
+
+    fn set_query(&mut self, query: String) {
+        let regex = search_regex(&query);
+        self.search = Some(Search {
+            query,
+            regex,
+            editing: true,
+        });
+        // incremental search: jump to the first match at or after the selection
+        if !self
+            .rows
+            .get(self.selected)
+            .is_some_and(|r| self.matches(r))
+        {
+            self.next_match(true);
+        }
+    }
+
+    fn next_match(&mut self, forward: bool) {
+        let len = self.rows.len();
+        for step in 1..=len {
+            let i = if forward {
+                (self.selected + step) % len
+            } else {
+                (self.selected + len - step) % len
+            };
+            if self.matches(&self.rows[i]) {
+                self.selected = i;
+                self.follow = false;
+                return;
+            }
+        }
+    }
+
+    /// (position of the selected row among the matches, number of matching rows)
+    fn match_position(&self) -> (Option<usize>, usize) {
+        let mut position = None;
+        let mut count = 0;
+        for (i, row) in self.rows.iter().enumerate() {
+            if self.matches(row) {
+                if i == self.selected {
+                    position = Some(count);
+                }
+                count += 1;
+            }
+        }
+        (position, count)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, page: usize) {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.quit = true;
+            return;
+        }
+        if let Some(search) = self.search.as_mut().filter(|s| s.editing) {
+            match key.code {
+                KeyCode::Enter => search.editing = false,
+                KeyCode::Esc => self.search = None,
+                KeyCode::Backspace => {
+                    let mut query = search.query.clone();
+                    query.pop();
+                    self.set_query(query);
+                }
+                KeyCode::Char(c) => {
+                    let query = format!("{}{}", search.query, c);
+                    self.set_query(query);
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        let page = page.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if self.search.is_some() => self.search = None,
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-page),
+            KeyCode::PageDown => self.move_by(page),
+            KeyCode::Home | KeyCode::Char('g') => self.select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.select_last(),
+            KeyCode::Char('/') => self.set_query(String::new()),
+            KeyCode::Char('n') => self.next_match(true),
+            KeyCode::Char('N') => self.next_match(false),
+            KeyCode::Char('e') => {
+                self.set_filter_types(!self.filter.errors_only, self.filter.warnings_only)
+            }
+            KeyCode::Char('w') => {
+                self.set_filter_types(self.filter.errors_only, !self.filter.warnings_only)
+            }
+            KeyCode::Char('a') => self.set_filter_types(false, false),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle_collapse(),
+            KeyCode::Char('c') => self.collapse_all(true),
+            KeyCode::Char('x') => self.collapse_all(false),
+            KeyCode::Char('d') => self.show_detail = !self.show_detail,
+            _ => {}
+        }
+    }
+
+    /// scroll just enough to keep the selection on screen
+    fn scroll_to_selection(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if height > 0 && self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        self.offset = self.offset.min(self.rows.len().saturating_sub(height));
+    }
+}
+
+/// convert an fmrl style to a ratatui style. Colors are left out when `color` is false.
+fn to_tui_style(style: &style::Style, color: bool) -> Style {
+    let to_color = |c: &ColorType| match c {
+        ColorType::Rgb(r, g, b) => Some(Color::Rgb(*r, *g, *b)),
+        ColorType::Ansi256(i) => Some(Color::Indexed(*i)),
+        ColorType::Ansi(name) => colored::Color::from_str(name).ok().map(|c| match c {
+            colored::Color::Black => Color::Black,
+            colored::Color::Red => Color::Red,
+            colored::Color::Green => Color::Green,
+            colored::Color::Yellow => Color::Yellow,
+            colored::Color::Blue => Color::Blue,
+            colored::Color::Magenta => Color::Magenta,
+            colored::Color::Cyan => Color::Cyan,
+            colored::Color::White => Color::Gray,
+            colored::Color::BrightBlack => Color::DarkGray,
+            colored::Color::BrightRed => Color::LightRed,
+            colored::Color::BrightGreen => Color::LightGreen,
+            colored::Color::BrightYellow => Color::LightYellow,
+            colored::Color::BrightBlue => Color::LightBlue,
+            colored::Color::BrightMagenta => Color::LightMagenta,
+            colored::Color::BrightCyan => Color::LightCyan,
+            colored::Color::BrightWhite => Color::White,
+            colored::Color::AnsiColor(i) => Color::Indexed(i),
+            colored::Color::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+        }),
+    };
+    let mut res = Style::default();
+    if color {
+        if let Some(fg) = style.foreground.as_ref().and_then(to_color) {
+            res = res.fg(fg);
+        }
+        if let Some(bg) = style.background.as_ref().and_then(to_color) {
+            res = res.bg(bg);
+        }
+    }
+    for attribute in &style.attributes {
+        res = res.add_modifier(match attribute {
+            style::Attribute::Bold => Modifier::BOLD,
+            style::Attribute::Dimmed => Modifier::DIM,
+            style::Attribute::Italic => Modifier::ITALIC,
+            style::Attribute::Underline => Modifier::UNDERLINED,
+            style::Attribute::Reversed => Modifier::REVERSED,
+        });
+    }
+    res
+}
+
+/// multi-line messages would break the one-row-per-line layout, so show line breaks as a symbol
+fn single_line(text: &str) -> String {
+    text.replace("\r\n", " ⏎ ").replace(['\r', '\n'], " ⏎ ")
+}
+
+/// Split styled segments further so search matches are drawn reversed
+fn highlight_search(segments: Vec<(String, Style)>, regex: Option<&Regex>) -> Vec<Span<'static>> {
+    let Some(regex) = regex else {
+        return segments
+            .into_iter()
+            .map(|(text, style)| Span::styled(text, style))
+            .collect();
+    };
+    let text: String = segments.iter().map(|(t, _)| t.as_str()).collect();
+    let matches: Vec<(usize, usize)> = regex
+        .find_iter(&text)
+        .map(|m| (m.start(), m.end()))
+        .collect();
+
+    let mut spans = vec![];
+    let mut start = 0;
+    for (segment, style) in segments {
+        let end = start + segment.len();
+        let mut boundaries = vec![start, end];
+        for (s, e) in &matches {
+            boundaries.extend([*s, *e].into_iter().filter(|b| start < *b && *b < end));
+        }
+        boundaries.sort_unstable();
+        for window in boundaries.windows(2) {
+            let (s, e) = (window[0], window[1]);
+            let is_match = matches.iter().any(|(ms, me)| *ms <= s && e <= *me);
+            let style = if is_match {
+                style.add_modifier(Modifier::REVERSED)
+            } else {
+                style
+            };
+            spans.push(Span::styled(text[s..e].to_string(), style));
+        }
+        start = end;
+    }
+    spans
+}
+
+struct View<'a> {
+    path: &'a Path,
+    styles: &'a LineStyles,
+    highlighter: &'a Highlighter,
+    color: bool,
+    watch: bool,
+}
+
+impl View<'_> {
+    fn row_line(&self, app: &App, row: &Row, selected: bool) -> Line<'static> {
+        let entry = &app.entries[row.entry];
+        let operation = entry.operation.map(|i| &app.operations[i]);
+        let gutter = match (selected, row.collapsed, entry.line.operation_start) {
+            (true, _, _) => "› ",
+            (false, true, _) => "▸ ",
+            (false, false, true) => "▾ ",
+            _ => "  ",
+        };
+        let mut segments = vec![(
+            gutter.to_string(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        let to_segments =
+            |text: &str, base: &style::Style, highlight: bool| -> Vec<(String, Style)> {
+                let text = single_line(text);
+                if highlight {
+                    self.highlighter
+                        .segments(&text, base)
+                        .into_iter()
+                        .map(|(t, s)| (t.to_string(), to_tui_style(&s, self.color)))
+                        .collect()
+                } else {
+                    vec![(text.clone(), to_tui_style(base, self.color))]
+                }
+            };
+        match (
+            &entry.line.line,
+            self.styles.for_line(&entry.line.line, &entry.line.actions),
+        ) {
+            (
+                LineType::Success(l)
+                | LineType::Error(l)
+                | LineType::Warning(l)
+                | LineType::Header(l),
+                Some(style),
+            ) => {
+                let style = match &entry.line.actions.highlight {
+                    Some(highlight) => Cow::Owned(style.highlighted(highlight)),
+                    None => Cow::Borrowed(style),
+                };
+                segments.extend(to_segments(&l.timestamp, &style.timestamp, false));
+                segments.push(("  ".to_string(), Style::default()));
+                segments.extend(to_segments(&l.filename, &style.filename, false));
+                segments.push(("  ".to_string(), Style::default()));
+                segments.extend(to_segments(&l.code, &style.error, false));
+                segments.push(("  ".to_string(), Style::default()));
+                segments.extend(to_segments(&l.message, &style.message, true));
+            }
+            (line, _) => segments.extend(to_segments(&line.to_string(), &self.styles.other, true)),
+        }
+        if !entry.line.actions.tags.is_empty() {
+            segments.push((
+                entry.line.actions.tag_text(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        }
+        if let (true, Some(op)) = (row.collapsed, operation) {
+            segments.push((
+                format!(
+                    "  [{} lines, {} errors, {} warnings]",
+                    op.len, op.errors, op.warnings
+                ),
+                Style::default().add_modifier(Modifier::DIM),
+            ));
+        }
+        let regex = app.search.as_ref().and_then(|s| s.regex.as_ref());
+        let mut line = Line::from(highlight_search(segments, regex));
+        if selected {
+            line = line.patch_style(Style::default().add_modifier(Modifier::BOLD));
+        }
+        line
+    }
+
+    fn detail(&self, app: &App) -> Vec<Line<'static>> {
+        let Some(entry) = app.selected_entry().map(|i| &app.entries[i]) else {
+            return vec![];
+        };
+        let kind = match &entry.line.line {
+            LineType::Error(_) if entry.line.actions.is_some() => {
+                format!("error (rules: {})", entry.line.actions.names().join(", "))
+            }
+            LineType::Error(_) => "error".to_string(),
+            LineType::Warning(_) => "warning".to_string(),
+            LineType::Success(_) => "success".to_string(),
+            LineType::Header(_) => "header".to_string(),
+            LineType::Other(_) => "other".to_string(),
+        };
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{name:<10}"),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(value),
+            ])
+        };
+        let mut lines = vec![field("type", kind)];
+        if let Some(i) = entry.operation {
+            let op = &app.operations[i];
+            lines.push(field(
+                "operation",
+                format!(
+                    "{} of {}, line {} of {}",
+                    i + 1,
+                    app.operations.len(),
+                    app.selected_entry().unwrap() - op.first + 1,
+                    op.len
+                ),
+            ));
+        }
+        match &entry.line.line {
+            LineType::Success(l)
+            | LineType::Error(l)
+            | LineType::Warning(l)
+            | LineType::Header(l) => {
+                lines.push(field("timestamp", entry.line.raw_timestamp.clone()));
+                lines.push(field("location", l.filename.clone()));
+                lines.push(field("code", l.code.clone()));
+                let mut message = l.message.lines();
+                lines.push(field(
+                    "message",
+                    message.next().unwrap_or_default().to_string(),
+                ));
+                lines.extend(message.map(|m| Line::raw(format!("{:10}{m}", ""))));
+            }
+            LineType::Other(l) => lines.push(field("text", l.clone())),
+        }
+        lines
+    }
+
+    fn status(&self, app: &App) -> Line<'static> {
+        let filter = match (app.filter.errors_only, app.filter.warnings_only) {
+            (true, true) => "errors + warnings",
+            (true, false) => "errors",
+            (false, true) => "warnings",
+            (false, false) => "all",
+        };
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let (error_style, warning_style) = if self.color {
+            (bold.fg(Color::Red), bold.fg(Color::Yellow))
+        } else {
+            (bold, bold)
+        };
+        let mut spans = vec![
+            Span::styled(" fmrl ", bold.add_modifier(Modifier::REVERSED)),
+            Span::raw(format!(
+                " {} │ {} lines │ ",
+                self.path.display(),
+                app.entries.len()
+            )),
+            Span::styled(format!("{} errors", app.errors), error_style),
+            Span::raw(" │ "),
+            Span::styled(format!("{} warnings", app.warnings), warning_style),
+            Span::raw(format!(" │ showing {filter}")),
+            Span::raw(
+                if app.filter.include.is_empty() && app.filter.exclude.is_empty() {
+                    ""
+                } else {
+                    " (with patterns)"
+                },
+            ),
+        ];
+        if let Some(search) = app.search.as_ref().filter(|s| !s.query.is_empty()) {
+            let (position, count) = app.match_position();
+            let position = position
+                .map(|p| (p + 1).to_string())
+                .unwrap_or("-".to_string());
+            spans.push(Span::raw(format!(
+                " │ \"{}\" {position}/{count}",
+                search.query
+            )));
+        }
+        if self.watch && app.follow {
+            spans.push(Span::styled(" │ following", bold));
+        }
+        Line::from(spans)
+    }
+
+    fn draw(&self, frame: &mut Frame, app: &mut App) {
+        let detail_height = if app.show_detail { 8 } else { 0 };
+        let [list_area, detail_area, status_area, help_area] = Layout::vertical([
+            Constraint::Min(1),
+            Constraint::Length(detail_height),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let height = list_area.height as usize;
+        app.scroll_to_selection(height);
+        let lines = (app.offset..app.rows.len().min(app.offset + height))
+            .map(|i| self.row_line(app, &app.rows[i], i == app.selected))
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(lines), list_area);
+
+        if app.show_detail {
+            let block = Block::default().borders(Borders::TOP).title(" details ");
+            frame.render_widget(
+                Paragraph::new(self.detail(app))
+                    .block(block)
+                    .wrap(Wrap { trim: false }),
+                detail_area,
+            );
+        }
+
+        frame.render_widget(Paragraph::new(self.status(app)), status_area);
+        let help = match &app.search {
+            Some(search) if search.editing => Line::from(format!("/{}", search.query))
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+            _ => Line::from(HELP).style(Style::default().add_modifier(Modifier::DIM)),
+        };
+        frame.render_widget(Paragraph::new(help), help_area);
+        if let Some(search) = app.search.as_ref().filter(|s| s.editing) {
+            let x = help_area.x + 1 + search.query.chars().count() as u16;
+            frame.set_cursor_position((x.min(help_area.right().saturating_sub(1)), help_area.y));
+        }
+    }
+}
+
+/// Run the full-screen view until the user quits. Lines go through the same processor, rules and notifications as the plain output.
+pub(crate) fn run(options: TuiOptions) -> crate::CustomResult {
+    let mut terminal = ratatui::init();
+    let res = run_app(&mut terminal, options);
+    ratatui::restore();
+    res
+}
+
+fn run_app(terminal: &mut DefaultTerminal, options: TuiOptions) -> crate::CustomResult {
+    let TuiOptions {
+        path,
+        mut log_file,
+        mut processor,
+        styles,
+        highlighter,
+        filter,
+        watch,
+        notif_tx,
+        send_notif,
+        color,
+    } = options;
+    let view = View {
+        path,
+        styles: &styles,
+        highlighter: &highlighter,
+        color,
+        watch,
+    };
+    let mut app = App::new(filter);
+
+    // don't send notifications for the initial file content. It might be a ton of old errors and warnings
+    for line in log_file.read_new()?.lines() {
+        app.push(processor.process(line));
+    }
+    app.refresh();
+
+    let watcher = watch.then(|| log_file::watch(path));
+    let mut list_height = 0;
+    while !app.quit {
+        terminal.draw(|frame| {
+            list_height = frame.area().height.saturating_sub(2) as usize;
+            view.draw(frame, &mut app);
+        })?;
+
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key, list_height);
+                }
+            }
+        }
+
+        let Some((_, rx)) = &watcher else {
+            continue;
+        };
+        let mut changed = false;
+        loop {
+            match rx.try_recv() {
+                Ok(Ok(_)) => changed = true,
+                Ok(Err(err)) => return Err(format!("{:?}", err).into()),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err("file watcher stopped".into()),
+            }
+        }
+        if changed {
+            for line in log_file.read_new()?.lines() {
+                let line = processor.process(line);
+                if send_notif && app.filter.shows(&line) {
+                    if let Some(notification) = line.notification {
+                        notif_tx.send(notification).unwrap();
+                    }
+                }
+                app.push(line);
+            }
+            app.refresh();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Args;
+
+    fn app_from_log(log: &str) -> App {
+        let mut processor = LineProcessor::new(&Args::default());
+        let mut app = App::new(LineFilter::default());
+        for line in log.lines() {
+            app.push(processor.process(line));
+        }
+        app.refresh();
+        app
+    }
+
+    fn en_log() -> String {
+        std::fs::read_to_string("tests/inputs/en-Import.log").unwrap()
+    }
+
+    #[test]
+    fn counts_errors_warnings_and_operations() {
+        let app = app_from_log(&en_log());
+        assert_eq!(app.errors, 2);
+        assert_eq!(app.warnings, 9);
+        assert_eq!(app.operations.len(), 12);
+        assert_eq!(app.rows.len(), app.entries.len());
+        // follows the end of the log by default
+        assert_eq!(app.selected, app.rows.len() - 1);
+    }
+
+    #[test]
+    fn filters_and_collapses_rows() {
+        let mut app = app_from_log(&en_log());
+        app.set_filter_types(true, false);
+        // errors plus the header
+        assert_eq!(app.rows.len(), 3);
+
+        app.set_filter_types(false, false);
+        app.collapse_all(true);
+        let before_first_op = app.operations[0].first;
+        assert_eq!(app.rows.len(), before_first_op + app.operations.len());
+        assert!(app
+            .rows
+            .iter()
+            .skip(before_first_op)
+            .all(|row| row.collapsed));
+
+        app.collapse_all(false);
+        assert_eq!(app.rows.len(), app.entries.len());
+    }
+
+    #[test]
+    fn search_jumps_between_matches() {
+        let mut app = app_from_log(&en_log());
+        app.select_first();
+        app.set_query("ALREADY EXISTS".to_string());
+        let (position, count) = app.match_position();
+        assert_eq!(position, Some(0));
+        assert!(count > 1);
+        let first = app.selected;
+        app.next_match(true);
+        assert!(app.selected > first);
+        app.next_match(false);
+        assert_eq!(app.selected, first);
+    }
+
+    #[test]
+    fn search_highlight_splits_segments() {
+        let regex = Regex::new("lo w").unwrap();
+        let spans = highlight_search(
+            vec![
+                ("hello".to_string(), Style::default()),
+                (" world".to_string(), Style::default()),
+            ],
+            Some(&regex),
+        );
+        let texts = spans
+            .iter()
+            .map(|s| s.content.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["hel", "lo", " w", "orld"]);
+        assert!(spans[1].style.add_modifier.contains(Modifier::REVERSED));
+        assert!(!spans[0].style.add_modifier.contains(Modifier::REVERSED));
+    }
+}
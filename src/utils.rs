@@ -20,6 +20,14 @@ pub(crate) fn is_timestamp(s: &str) -> bool {
     parse_datetime(s.as_bytes()).is_ok()
 }
 
+pub(crate) fn get_s(n: usize) -> &'static str {
+    if n == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
 pub(crate) fn clear_terminal() {
     let _ = clearscreen::clear();
 }